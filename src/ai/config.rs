@@ -46,7 +46,8 @@ pub const AI_MIN_FLEE_DISTANCE: f32 = 200.0;
 
 /// Raycast distance for checking direction obstructions (pixels).
 ///
-/// Used when testing 16 directions for valid movement paths.
+/// Used when testing 16 directions for valid movement paths, by sweeping the agent's
+/// collision circle this far.
 /// Longer distances detect obstacles earlier but are more expensive.
 /// Should be long enough to detect nearby walls but not so long as to be wasteful.
 pub const AI_RAYCAST_DISTANCE: f32 = 100.0;
@@ -4,9 +4,9 @@ use rand::Rng;
 
 use crate::{
     collisions::s_collision,
-    spatial::SpatialGrid,
-    utils::{lerp, line_intersect},
-    GizmosVisible, Physics, PlayerPosition,
+    spatial::{EdgeQuery, SpatialGrid},
+    utils::lerp,
+    GizmosVisible, Level, Physics, PlayerPosition,
 };
 
 use super::config::{
@@ -54,17 +54,26 @@ pub(crate) struct SystemCache {
     frame_count: u32,
     // Direction vectors cache
     direction_vectors: Option<[Vec2; 16]>,
+    // Reused by every spatial grid query
+    edge_query: EdgeQuery,
 }
 
 /// Plugin for Flee AI behavior system.
 ///
 /// Registers the AI movement system to run before collision detection,
-/// ensuring AI movement is processed before physics resolution.
+/// ensuring AI movement is processed before physics resolution, followed by
+/// the system colouring agents by their new blend.
 pub struct FleeAIPlugin;
 
 impl Plugin for FleeAIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, s_flee_ai_movement.before(s_collision));
+        app.add_systems(Update, s_flee_ai_movement.before(s_collision))
+            .add_systems(
+                Update,
+                s_flee_ai_color
+                    .after(s_flee_ai_movement)
+                    .before(s_collision),
+            );
     }
 }
 
@@ -79,10 +88,8 @@ impl Plugin for FleeAIPlugin {
 ///   Used to select the best unobstructed movement direction.
 /// * `wander_angle` - Persistent angle for smooth wandering behavior.
 ///   Updated each frame with random displacement to create organic movement.
-/// * `color` - Visual indicator of current behavior state:
-///   - Red (0.0): Pure flee behavior
-///   - Orange (0.5): Mixed behavior
-///   - Green (1.0): Pure wander behavior
+/// * `color` - Visual indicator of current behavior state, blended between the level
+///   theme's flee colour (0.0, red by default) and wander colour (1.0, green by default)
 /// * `blend` - Blend factor between flee (0.0) and wander (1.0) behaviors.
 ///   Calculated based on player distance and line-of-sight.
 #[derive(Component)]
@@ -93,6 +100,13 @@ pub struct FleeAI {
     pub blend: f32,
 }
 
+/// Colour each AI agent by its blend between fleeing and wandering, using the level theme
+pub fn s_flee_ai_color(mut flee_ai_query: Query<&mut FleeAI>, level: Res<Level>) {
+    for mut ai_data in flee_ai_query.iter_mut() {
+        ai_data.color = level.theme.creature_color(ai_data.blend);
+    }
+}
+
 /// Main system for Flee AI movement behavior.
 ///
 /// Processes all AI agents each frame, calculating:
@@ -132,24 +146,19 @@ pub fn s_flee_ai_movement(
         // Complexity: O(nearby_edges) instead of O(all_edges)
         let can_see_player = {
             // Use cached result if positions haven't changed significantly
-            if !player_moved
-                && (ai_pos - cache.last_ai_pos).length_squared()
-                    < LOS_CACHE_THRESHOLD * LOS_CACHE_THRESHOLD
-                && cache.cached_los_result.is_some()
-            {
-                cache.cached_los_result.unwrap()
+            let cached = cache.cached_los_result.filter(|_| {
+                !player_moved
+                    && (ai_pos - cache.last_ai_pos).length_squared()
+                        < LOS_CACHE_THRESHOLD * LOS_CACHE_THRESHOLD
+            });
+            if let Some(cached) = cached {
+                cached
             } else {
-                // Perform spatial raycast
-                let edges = spatial_grid.edges_along_ray(ai_pos, player_pos.position);
-                let mut can_see = true;
-
-                // Only test edges along the ray path (optimized)
-                for edge in edges {
-                    if line_intersect(edge.start, edge.end, ai_pos, player_pos.position).is_some() {
-                        can_see = false;
-                        break;
-                    }
-                }
+                // Perform spatial raycast, stopping at the first wall in the way
+                let to_player = player_pos.position - ai_pos;
+                let can_see = spatial_grid
+                    .raycast(ai_pos, to_player, to_player.length(), &mut cache.edge_query)
+                    .is_none();
 
                 // Update cache
                 cache.last_player_pos = player_pos.position;
@@ -182,8 +191,6 @@ pub fn s_flee_ai_movement(
             }
         };
 
-        ai_data.color = Color::srgb(1.0 - blend, blend, 0.0);
-
         if gizmos_visible.visible {
             gizmos.line_2d(ai_pos, player_pos.position, ai_data.color);
         }
@@ -229,7 +236,7 @@ pub fn s_flee_ai_movement(
         }
 
         // Get the dir with the highest weight that's not obstructed
-        // Complexity: O(16 log 16) sort + O(16 × nearby_edges) raycasts (optimized with spatial grid)
+        // Complexity: O(16 log 16) sort + O(16 × nearby_edges) circle sweeps (optimized with spatial grid)
         let actual_dir = {
             // Use pre-computed indices array and sort by weight
             let mut dir_indices = DIR_INDICES;
@@ -244,24 +251,21 @@ pub fn s_flee_ai_movement(
             let mut found_valid_dir = false;
 
             // Early exit: stop after finding first good direction
-            let dir_vectors = cache
+            let dir_vectors = *cache
                 .direction_vectors
                 .get_or_insert_with(get_direction_vectors);
             for &dir_idx in &dir_indices {
                 let dir = dir_vectors[dir_idx];
-                let ray_end = ai_pos + dir * AI_RAYCAST_DISTANCE;
-
-                // Use spatial grid to only test edges along ray path
-                let edges = spatial_grid.edges_along_ray(ai_pos, ray_end);
-                let mut obstructed = false;
-
-                // Early exit: break immediately when obstruction found
-                for edge in edges {
-                    if line_intersect(edge.start, edge.end, ai_pos, ray_end).is_some() {
-                        obstructed = true;
-                        break;
-                    }
-                }
+                // Sweep the agent's whole body, so gaps narrower than it count as walls
+                let obstructed = spatial_grid
+                    .sweep_circle(
+                        ai_pos,
+                        dir,
+                        AI_RAYCAST_DISTANCE,
+                        ai_physics.radius,
+                        &mut cache.edge_query,
+                    )
+                    .is_some();
 
                 if !obstructed {
                     actual_dir = dir;
@@ -385,7 +389,7 @@ pub fn get_wander_dir(
 ///
 /// # Visualization
 ///
-/// * Colored circle: AI entity (red=flee, green=wander)
+/// * Colored circle: AI entity (theme flee colour to wander colour, red to green by default)
 /// * Surface normal line: White line showing collision normal (if gizmos visible)
 /// * Direction weights: 16 lines showing weight vectors (if gizmos visible)
 ///   - Green: Positive weights (preferred directions)
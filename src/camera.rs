@@ -0,0 +1,78 @@
+use ::bevy::prelude::*;
+use bevy::input::mouse::{AccumulatedMouseScroll, MouseScrollUnit};
+
+use crate::{collisions::s_collision, Level, Player};
+
+// Camera parameters
+/// How quickly the camera catches up with the player, higher is snappier
+const CAMERA_SMOOTHING: f32 = 5.0;
+/// Zoom factor for one notch of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+/// Pixel scroll deltas (touchpads) per wheel notch
+const PIXELS_PER_LINE: f32 = 100.0;
+const MIN_ZOOM_SCALE: f32 = 0.25;
+const MAX_ZOOM_SCALE: f32 = 4.0;
+
+/// Plugin for a camera that follows the player and zooms with the mouse wheel
+///
+/// The camera eases towards the player and stays inside the level, so the space past the
+/// walls is only shown when the level is smaller than the view.
+pub struct FollowCameraPlugin;
+
+impl Plugin for FollowCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_camera_zoom).add_systems(
+            Update,
+            s_camera_follow.after(s_collision).after(s_camera_zoom),
+        );
+    }
+}
+
+pub fn s_camera_zoom(
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    mut projection: Single<&mut Projection, With<Camera2d>>,
+) {
+    let notches = match mouse_scroll.unit {
+        MouseScrollUnit::Line => mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => mouse_scroll.delta.y / PIXELS_PER_LINE,
+    };
+    if notches == 0.0 {
+        return;
+    }
+
+    if let Projection::Orthographic(orthographic) = projection.as_mut() {
+        // Scrolling up zooms in
+        orthographic.scale =
+            (orthographic.scale * ZOOM_STEP.powf(-notches)).clamp(MIN_ZOOM_SCALE, MAX_ZOOM_SCALE);
+    }
+}
+
+pub fn s_camera_follow(
+    time: Res<Time>,
+    level: Res<Level>,
+    player_query: Query<&Transform, (With<Player>, Without<Camera2d>)>,
+    camera_query: Single<(&mut Transform, &Projection), With<Camera2d>>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+    let (mut camera_transform, projection) = camera_query.into_inner();
+
+    // Frame rate independent easing
+    let t = 1.0 - (-CAMERA_SMOOTHING * time.delta_secs()).exp();
+    let position = camera_transform
+        .translation
+        .xy()
+        .lerp(player_transform.translation.xy(), t);
+
+    // Keep the view inside the level, centring it on any axis the level doesn't fill
+    let level_half_size = level.half_size * level.grid_size;
+    let view_half_size = match projection {
+        Projection::Orthographic(orthographic) => orthographic.area.half_size(),
+        _ => Vec2::ZERO,
+    };
+    let max_offset = (level_half_size - view_half_size).max(Vec2::ZERO);
+    let position = position.clamp(-max_offset, max_offset);
+
+    camera_transform.translation = position.extend(camera_transform.translation.z);
+}
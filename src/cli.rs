@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use crate::level::{
+    asset::{baked_level_path, parse_level_file, read_level_file},
+    bake::bake_level,
+    format::DEFAULT_GRID_SIZE,
+    generate::{generate_level, GeneratorKind},
+    generate_level_polygons,
+    svg::polygons_to_svg,
+    validate_level, LevelData, Polygon,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Subcommand that writes a level's polygons to an SVG
+const EXPORT_SVG_SUBCOMMAND: &str = "export-svg";
+/// Subcommand that checks level files load and extract cleanly
+const VALIDATE_SUBCOMMAND: &str = "validate";
+/// Subcommand that writes each level's extracted geometry next to it
+const BAKE_SUBCOMMAND: &str = "bake";
+/// Subcommand that writes a batch of generated levels to a directory
+const GENERATE_SUBCOMMAND: &str = "generate";
+
+/// Columns and rows of the levels written by the generate subcommand
+const GENERATED_LEVEL_SIZE: (usize, usize) = (40, 30);
+
+/// Run a subcommand given on the command line, without opening a window
+///
+/// Returns the process exit code, or `None` if the arguments aren't a subcommand and the
+/// game should start.
+pub fn run_subcommand(args: &[String]) -> Option<i32> {
+    match args {
+        [subcommand, level_path, svg_path] if subcommand == EXPORT_SVG_SUBCOMMAND => {
+            Some(export_svg(level_path, svg_path))
+        }
+        [subcommand, ..] if subcommand == EXPORT_SVG_SUBCOMMAND => {
+            eprintln!("usage: {EXPORT_SVG_SUBCOMMAND} <level> <output.svg>");
+            Some(2)
+        }
+        [subcommand, level_paths @ ..] if subcommand == VALIDATE_SUBCOMMAND => {
+            if level_paths.is_empty() {
+                eprintln!("usage: {VALIDATE_SUBCOMMAND} <level>...");
+                return Some(2);
+            }
+            Some(validate(level_paths))
+        }
+        [subcommand, level_paths @ ..] if subcommand == BAKE_SUBCOMMAND => {
+            if level_paths.is_empty() {
+                eprintln!("usage: {BAKE_SUBCOMMAND} <level>...");
+                return Some(2);
+            }
+            Some(bake(level_paths))
+        }
+        [subcommand, generate_args @ ..] if subcommand == GENERATE_SUBCOMMAND => {
+            let [style, seed, count, output_dir] = generate_args else {
+                eprintln!("usage: {GENERATE_SUBCOMMAND} <caves|rooms> <seed> <count> <output dir>");
+                return Some(2);
+            };
+            let kind = match style.as_str() {
+                "caves" => Some(GeneratorKind::Caves),
+                "rooms" => Some(GeneratorKind::Rooms),
+                _ => None,
+            };
+            match (kind, seed.parse(), count.parse()) {
+                (Some(kind), Ok(seed), Ok(count)) => {
+                    Some(generate(kind, style, seed, count, output_dir))
+                }
+                _ => {
+                    eprintln!(
+                        "{GENERATE_SUBCOMMAND}: expected caves or rooms, then a seed and a count"
+                    );
+                    Some(2)
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Read a level file in any format and extract its polygons
+fn load_level(level_path: &str) -> Result<(LevelData, Vec<Polygon>), String> {
+    let bytes = std::fs::read(level_path).map_err(|err| err.to_string())?;
+    let level_data = parse_level_file(level_path, &bytes).map_err(|err| err.to_string())?;
+    let (polygons, _, _) = generate_level_polygons(&level_data).map_err(|err| err.to_string())?;
+    Ok((level_data, polygons))
+}
+
+fn export_svg(level_path: &str, svg_path: &str) -> i32 {
+    let result = load_level(level_path).and_then(|(level_data, polygons)| {
+        let svg = polygons_to_svg(&polygons, level_data.size() * level_data.grid_size);
+        std::fs::write(svg_path, svg).map_err(|err| err.to_string())
+    });
+
+    match result {
+        Ok(()) => {
+            println!("Exported \"{level_path}\" to \"{svg_path}\"");
+            0
+        }
+        Err(err) => {
+            eprintln!("Failed to export \"{level_path}\": {err}");
+            1
+        }
+    }
+}
+
+/// Check each level, printing every problem in it and failing if any level has one
+fn validate(level_paths: &[String]) -> i32 {
+    let mut failures = 0;
+
+    for level_path in level_paths {
+        match level_problems(level_path) {
+            Ok(polygon_count) => println!("{level_path}: ok, {polygon_count} polygons"),
+            Err(problems) => {
+                for problem in problems {
+                    eprintln!("{level_path}: {problem}");
+                }
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        eprintln!(
+            "{failures} of {} levels failed validation",
+            level_paths.len()
+        );
+        1
+    } else {
+        0
+    }
+}
+
+/// Number of polygons extracted from a level file, or everything wrong with it
+///
+/// Files that can't be read or parsed at all only have that one problem.
+fn level_problems(level_path: &str) -> Result<usize, Vec<String>> {
+    let level_data = std::fs::read(level_path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| read_level_file(level_path, &bytes).map_err(|err| err.to_string()))
+        .map_err(|err| vec![err])?;
+
+    validate_level(&level_data)
+        .map(|polygons| polygons.len())
+        .map_err(|problems| problems.iter().map(ToString::to_string).collect())
+}
+
+/// Bake each level's geometry to `<level>.bake`, which the game loads instead of extracting
+fn bake(level_paths: &[String]) -> i32 {
+    let mut failures = 0;
+
+    for level_path in level_paths {
+        let baked_path = baked_level_path(level_path);
+        let result = std::fs::read(level_path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| parse_level_file(level_path, &bytes).map_err(|err| err.to_string()))
+            .and_then(|level_data| bake_level(&level_data).map_err(|err| err.to_string()))
+            .and_then(|blob| std::fs::write(&baked_path, blob).map_err(|err| err.to_string()));
+
+        match result {
+            Ok(()) => println!("Baked \"{level_path}\" to \"{baked_path}\""),
+            Err(err) => {
+                eprintln!("Failed to bake \"{level_path}\": {err}");
+                failures += 1;
+            }
+        }
+    }
+
+    i32::from(failures > 0)
+}
+
+/// Write `count` generated levels to `<output dir>/<style>-<seed>.json`, one per seed from
+/// `first_seed` up
+fn generate(
+    kind: GeneratorKind,
+    style: &str,
+    first_seed: u64,
+    count: u64,
+    output_dir: &str,
+) -> i32 {
+    if let Err(err) = std::fs::create_dir_all(output_dir) {
+        eprintln!("Failed to create \"{output_dir}\": {err}");
+        return 1;
+    }
+
+    let (width, height) = GENERATED_LEVEL_SIZE;
+    let mut failures = 0;
+
+    for seed in (first_seed..).take(count as usize) {
+        let level_data = generate_level(kind, width, height, seed, DEFAULT_GRID_SIZE);
+        let level_path = Path::new(output_dir).join(format!("{style}-{seed}.json"));
+
+        match std::fs::write(&level_path, level_data.to_json()) {
+            Ok(()) => println!("Generated \"{}\"", level_path.display()),
+            Err(err) => {
+                eprintln!("Failed to write \"{}\": {err}", level_path.display());
+                failures += 1;
+            }
+        }
+    }
+
+    i32::from(failures > 0)
+}
@@ -0,0 +1,113 @@
+//! Tests for the subcommands
+//!
+//! Levels are written to the system temp directory, prefixed with the process ID so parallel
+//! test runs don't share files.
+
+use super::*;
+
+const VALID_LEVEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/level.json");
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
+/// Write a level file to the temp directory, returning its path
+fn temp_level(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn validate_passes_valid_levels() {
+    assert_eq!(
+        run_subcommand(&args(&[VALIDATE_SUBCOMMAND, VALID_LEVEL_PATH])),
+        Some(0)
+    );
+    assert!(matches!(level_problems(VALID_LEVEL_PATH), Ok(count) if count > 0));
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    // A ragged row, an unknown tile and a free-form polygon with too few points
+    let path = temp_level(
+        "invalid-level.json",
+        r#"{
+            "version": 2,
+            "tiles": [[1, 1, 1], [1, 0], [1, 12, 1]],
+            "polygons": [{ "points": [[0, 0], [1, 0], [0, 0]] }]
+        }"#,
+    );
+
+    assert_eq!(
+        run_subcommand(&args(&[VALIDATE_SUBCOMMAND, VALID_LEVEL_PATH, &path])),
+        Some(1)
+    );
+    let problems = level_problems(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        problems,
+        [
+            "row 1 has 2 tiles, expected 3 to match the first row",
+            "unknown tile ID 12 at row 2, column 1",
+            "polygon 0 has 2 points, expected at least 3",
+        ]
+    );
+}
+
+#[test]
+fn validate_reports_unreadable_levels() {
+    let path = temp_level("unreadable-level.json", "[[1, 1], [1");
+    let problems = level_problems(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("failed to parse level JSON"));
+    assert_eq!(run_subcommand(&args(&[VALIDATE_SUBCOMMAND])), Some(2));
+}
+
+#[test]
+fn generate_writes_valid_levels_for_each_seed() {
+    let output_dir = std::env::temp_dir().join(format!("{}-generated", std::process::id()));
+    let output_dir = output_dir.to_string_lossy().into_owned();
+
+    assert_eq!(
+        run_subcommand(&args(&[
+            GENERATE_SUBCOMMAND,
+            "rooms",
+            "7",
+            "3",
+            &output_dir
+        ])),
+        Some(0)
+    );
+    let levels: Vec<LevelData> = (7..10)
+        .map(|seed| {
+            let path = format!("{output_dir}/rooms-{seed}.json");
+            let bytes = std::fs::read(&path).unwrap();
+            parse_level_file(&path, &bytes).unwrap()
+        })
+        .collect();
+    std::fs::remove_dir_all(&output_dir).unwrap();
+
+    for level_data in &levels {
+        assert!(validate_level(level_data).is_ok());
+    }
+    assert_ne!(levels[0].tiles, levels[1].tiles);
+
+    assert_eq!(
+        run_subcommand(&args(&[
+            GENERATE_SUBCOMMAND,
+            "mazes",
+            "7",
+            "3",
+            &output_dir
+        ])),
+        Some(2)
+    );
+    assert_eq!(
+        run_subcommand(&args(&[GENERATE_SUBCOMMAND, "caves", "seven"])),
+        Some(2)
+    );
+}
@@ -1,11 +1,18 @@
 use bevy::{
     app::{App, Plugin, Update},
-    ecs::system::{Query, Res},
+    ecs::system::{Local, Query, Res},
     math::{Vec2, Vec3Swizzles},
     transform::components::Transform,
 };
 
-use crate::{utils::line_intersect, Level, Physics};
+use crate::{
+    spatial::{EdgeQuery, SpatialGrid},
+    utils::line_intersect,
+    Level, Physics,
+};
+
+#[cfg(test)]
+mod tests;
 
 // Collision detection parameters
 const COLLISION_RAYCAST_DIR: Vec2 = Vec2::new(2.0, 1.0);
@@ -20,75 +27,74 @@ impl Plugin for CollisionPlugin {
     }
 }
 
-pub fn s_collision(mut entity_query: Query<(&mut Transform, &mut Physics)>, level: Res<Level>) {
+pub fn s_collision(
+    mut entity_query: Query<(&mut Transform, &mut Physics)>,
+    level: Res<Level>,
+    spatial_grid: Res<SpatialGrid>,
+    mut edge_query: Local<EdgeQuery>,
+    mut colliding_polygons: Local<Vec<u32>>,
+) {
     for (mut transform, mut physics) in entity_query.iter_mut() {
+        let position = transform.translation.xy();
         let mut adjustment = Vec2::ZERO;
         let mut new_normal = Vec2::ZERO;
+        colliding_polygons.clear();
 
-        for polygon in &level.polygons {
-            let mut intersect_counter = 0;
-            let mut colliding_with_polygon = false;
-
-            for line_index in 1..polygon.points.len() {
-                let start = polygon.points[line_index - 1];
-                let end = polygon.points[line_index];
-
-                // Intersection detection
-                {
-                    let intersection = line_intersect(
-                        start,
-                        end,
-                        transform.translation.xy(),
-                        transform.translation.xy()
-                            + COLLISION_RAYCAST_DIR * COLLISION_RAYCAST_DISTANCE,
-                    );
-
-                    if intersection.is_some() {
-                        intersect_counter += 1;
-                    }
-                }
+        // Only edges close enough to touch can affect the entity
+        let nearby_edges = spatial_grid.edges_in_circle(
+            position,
+            physics.radius + COLLISION_TOUCH_THRESHOLD,
+            &mut edge_query,
+        );
 
-                let previous_side_of_line =
-                    side_of_line_detection(start, end, physics.prev_position);
+        for edge in nearby_edges.iter().map(|&id| spatial_grid.edge(id)) {
+            let polygon = &level.polygons[edge.polygon as usize];
+            let (start, end) = (edge.start, edge.end);
 
-                if previous_side_of_line != polygon.collision_side {
-                    continue;
-                }
+            let previous_side_of_line = side_of_line_detection(start, end, physics.prev_position);
 
-                let (distance_sq, projection) =
-                    find_projection(start, end, transform.translation.xy());
+            if previous_side_of_line != polygon.collision_side {
+                continue;
+            }
 
-                let colliding_with_line = distance_sq <= physics.radius.powi(2);
-                colliding_with_polygon = colliding_with_polygon || colliding_with_line;
+            let (distance_sq, projection) = find_projection(start, end, position);
 
-                let touching_line =
-                    distance_sq <= (physics.radius + COLLISION_TOUCH_THRESHOLD).powi(2);
+            let colliding_with_line = distance_sq <= physics.radius.powi(2);
+            if colliding_with_line && !colliding_polygons.contains(&edge.polygon) {
+                colliding_polygons.push(edge.polygon);
+            }
 
-                if touching_line {
-                    let normal_dir = (transform.translation.xy() - projection).normalize_or_zero();
+            let touching_line = distance_sq <= (physics.radius + COLLISION_TOUCH_THRESHOLD).powi(2);
 
-                    // Add the normal dir to the players new normal
-                    new_normal -= normal_dir;
-                }
+            if touching_line {
+                let normal_dir = (position - projection).normalize_or_zero();
 
-                if colliding_with_line {
-                    let mut delta = (transform.translation.xy() - projection).normalize_or_zero();
+                // Add the normal dir to the players new normal
+                new_normal -= normal_dir;
+            }
 
-                    delta *= physics.radius - distance_sq.sqrt();
+            if colliding_with_line {
+                let mut delta = (position - projection).normalize_or_zero();
 
-                    if delta.x.abs() > adjustment.x.abs() {
-                        adjustment.x = delta.x;
-                    }
-                    if delta.y.abs() > adjustment.y.abs() {
-                        adjustment.y = delta.y;
-                    }
+                delta *= physics.radius - distance_sq.sqrt();
+
+                if delta.x.abs() > adjustment.x.abs() {
+                    adjustment.x = delta.x;
+                }
+                if delta.y.abs() > adjustment.y.abs() {
+                    adjustment.y = delta.y;
                 }
-            }
-            if colliding_with_polygon && intersect_counter % 2 == 1 {
-                transform.translation = physics.prev_position.extend(0.0);
             }
         }
 
+        // An entity that ended up inside a polygon it's colliding with moved through a wall
+        if colliding_polygons
+            .iter()
+            .any(|&polygon| inside_polygon(&spatial_grid, polygon, position, &mut edge_query))
+        {
+            transform.translation = physics.prev_position.extend(0.0);
+        }
+
         // Update the normal
         physics.normal = new_normal.normalize_or_zero();
 
@@ -102,6 +108,29 @@ pub fn s_collision(mut entity_query: Query<(&mut Transform, &mut Physics)>, leve
     }
 }
 
+/// Whether a position is inside a polygon, the ray from it crosses the polygon's edges an odd
+/// number of times
+///
+/// Only the edges in grid cells along the ray are tested, rather than every edge of the polygon.
+pub fn inside_polygon(
+    spatial_grid: &SpatialGrid,
+    polygon: u32,
+    position: Vec2,
+    edge_query: &mut EdgeQuery,
+) -> bool {
+    let end = position + COLLISION_RAYCAST_DIR * COLLISION_RAYCAST_DISTANCE;
+    let intersect_counter = spatial_grid
+        .edges_along_ray(position, end, edge_query)
+        .iter()
+        .map(|&id| spatial_grid.edge(id))
+        .filter(|edge| {
+            edge.polygon == polygon && line_intersect(edge.start, edge.end, position, end).is_some()
+        })
+        .count();
+
+    intersect_counter % 2 == 1
+}
+
 pub fn find_projection(start: Vec2, end: Vec2, point: Vec2) -> (f32, Vec2) {
     let point_vec = point - start;
     let line_vec = end - start;
@@ -0,0 +1,45 @@
+//! Tests for the wall collision checks
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::*;
+use crate::level::{
+    generate::{generate_level, GeneratorKind},
+    generate_level_polygons,
+};
+
+#[test]
+fn inside_polygon_matches_testing_every_edge() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut edge_query = EdgeQuery::default();
+
+    for (kind, seed) in [(GeneratorKind::Caves, 1), (GeneratorKind::Rooms, 2)] {
+        let level_data = generate_level(kind, 30, 20, seed, 16.0);
+        let (polygons, _, _) = generate_level_polygons(&level_data).unwrap();
+        let grid = SpatialGrid::new(&polygons, level_data.grid_size);
+
+        for _ in 0..500 {
+            // Spread over the level and a little way past its edges
+            let position = Vec2::new(
+                rng.random_range(-280.0..280.0),
+                rng.random_range(-200.0..200.0),
+            );
+            let end = position + COLLISION_RAYCAST_DIR * COLLISION_RAYCAST_DISTANCE;
+
+            for (i, polygon) in polygons.iter().enumerate() {
+                let full_scan = polygon
+                    .points
+                    .windows(2)
+                    .filter(|edge| line_intersect(edge[0], edge[1], position, end).is_some())
+                    .count()
+                    % 2
+                    == 1;
+                assert_eq!(
+                    inside_polygon(&grid, i as u32, position, &mut edge_query),
+                    full_scan,
+                    "polygon {i} at {position}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use ::bevy::prelude::*;
+use bevy::{color::palettes::css, window::PrimaryWindow};
+
+use crate::{
+    level::{
+        generate::{generate_level, GeneratorKind},
+        tile_shape, LevelData, MAX_TILE_ID,
+    },
+    s_apply_level, LevelHandle, LevelPath,
+};
+
+// Editor key bindings
+const EDITOR_TOGGLE_KEY: KeyCode = KeyCode::KeyE;
+const BRUSH_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+// Editor overlay colours
+const GRID_COLOR: Srgba = Srgba::new(1.0, 1.0, 1.0, 0.08);
+const TILE_COLOR: Srgba = Srgba::new(1.0, 1.0, 1.0, 0.25);
+const CURSOR_COLOR: Srgba = css::YELLOW;
+
+/// Plugin for the in-game tile editor.
+///
+/// Press E to toggle the editor. While it's open:
+/// * 0-9 select the tile ID to paint
+/// * Left mouse paints the selected tile, right mouse erases
+/// * Ctrl+S saves the level back to the file it was loaded from
+/// * C / R replace the level with a generated cave / room layout of the same size
+///
+/// Edits are written straight into the `LevelData` asset, so `s_apply_level` rebuilds
+/// the polygons and spatial grid the same way it does for a hot reload.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditorState {
+            active: false,
+            brush: 1,
+            seed: 0,
+        })
+        .add_systems(
+            Update,
+            (s_editor_toggle, s_editor_level_keys, s_editor_paint)
+                .chain()
+                .before(s_apply_level),
+        )
+        .add_systems(Update, s_editor_render);
+    }
+}
+
+#[derive(Resource)]
+pub struct EditorState {
+    pub active: bool,
+    pub brush: u32,
+    /// Seed for the next generated layout
+    pub seed: u64,
+}
+
+/// Marks the editor help text
+#[derive(Component)]
+pub struct EditorText;
+
+/// Toggle the editor and select the brush, keeping the help text up to date
+pub fn s_editor_toggle(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<EditorState>,
+    editor_text_query: Query<Entity, With<EditorText>>,
+) {
+    if keyboard_input.just_pressed(EDITOR_TOGGLE_KEY) {
+        editor.active = !editor.active;
+
+        for entity in &editor_text_query {
+            commands.entity(entity).despawn();
+        }
+        if editor.active {
+            commands.spawn((
+                Text::new(editor_help_text(editor.brush)),
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    ..default()
+                },
+                EditorText,
+            ));
+        }
+    }
+
+    if !editor.active {
+        return;
+    }
+
+    // Select brush
+    for (tile, key) in BRUSH_KEYS.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            editor.brush = tile as u32;
+            for entity in &editor_text_query {
+                commands
+                    .entity(entity)
+                    .insert(Text::new(editor_help_text(editor.brush)));
+            }
+        }
+    }
+}
+
+/// Save the level, or replace it with a generated layout
+pub fn s_editor_level_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<EditorState>,
+    level_handle: Res<LevelHandle>,
+    level_path: Res<LevelPath>,
+    mut level_assets: ResMut<Assets<LevelData>>,
+) {
+    if !editor.active {
+        return;
+    }
+
+    // Save
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keyboard_input.just_pressed(KeyCode::KeyS) {
+        if let Some(level_data) = level_assets.get(&level_handle.0) {
+            save_level(&level_path.path, level_data);
+        }
+    }
+
+    // Generate
+    let generator_kind = if keyboard_input.just_pressed(KeyCode::KeyC) {
+        Some(GeneratorKind::Caves)
+    } else if keyboard_input.just_pressed(KeyCode::KeyR) {
+        Some(GeneratorKind::Rooms)
+    } else {
+        None
+    };
+    if let Some(kind) = generator_kind {
+        if let Some(level_data) = level_assets.get_mut(&level_handle.0) {
+            let size = level_data.size();
+            info!("Generating {kind:?} level with seed {}", editor.seed);
+            let mut generated = generate_level(
+                kind,
+                size.x as usize,
+                size.y as usize,
+                editor.seed,
+                level_data.grid_size,
+            );
+            // Keep the level's look
+            generated.theme = level_data.theme;
+            generated.palette = std::mem::take(&mut level_data.palette);
+            generated.color_seed = level_data.color_seed;
+            *level_data = generated;
+            editor.seed += 1;
+        }
+    }
+}
+
+/// Paint the tile under the cursor with the brush, or erase it
+pub fn s_editor_paint(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    editor: Res<EditorState>,
+    level_handle: Res<LevelHandle>,
+    mut level_assets: ResMut<Assets<LevelData>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera_query: Single<(&Camera, &GlobalTransform)>,
+) {
+    if !editor.active {
+        return;
+    }
+
+    let tile = if mouse_input.pressed(MouseButton::Left) {
+        editor.brush
+    } else if mouse_input.pressed(MouseButton::Right) {
+        0
+    } else {
+        return;
+    };
+
+    let Some(cursor_position) = cursor_world_position(&window, &camera_query) else {
+        return;
+    };
+
+    let Some((column, row)) = level_assets
+        .get(&level_handle.0)
+        .and_then(|level_data| level_data.world_to_tile(cursor_position))
+    else {
+        return;
+    };
+
+    // Only take the asset mutably when the tile changes, since that marks it modified
+    let changed = level_assets
+        .get(&level_handle.0)
+        .is_some_and(|level_data| level_data.tiles[row][column] != tile);
+    if changed {
+        if let Some(level_data) = level_assets.get_mut(&level_handle.0) {
+            level_data.tiles[row][column] = tile;
+        }
+    }
+}
+
+pub fn s_editor_render(
+    mut gizmos: Gizmos,
+    editor: Res<EditorState>,
+    level_handle: Res<LevelHandle>,
+    level_assets: Res<Assets<LevelData>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera_query: Single<(&Camera, &GlobalTransform)>,
+) {
+    if !editor.active {
+        return;
+    }
+    let Some(level_data) = level_assets.get(&level_handle.0) else {
+        return;
+    };
+
+    // Draw the tile grid
+    let size = level_data.size();
+    gizmos.grid_2d(
+        Isometry2d::IDENTITY,
+        size.as_uvec2(),
+        Vec2::splat(level_data.grid_size),
+        GRID_COLOR,
+    );
+
+    // Draw every tile's shape
+    for (row, row_tiles) in level_data.tiles.iter().enumerate() {
+        for (column, &tile) in row_tiles.iter().enumerate() {
+            draw_tile(&mut gizmos, level_data, column, row, tile, TILE_COLOR);
+        }
+    }
+
+    // Preview the brush under the cursor
+    if let Some((column, row)) = cursor_world_position(&window, &camera_query)
+        .and_then(|cursor_position| level_data.world_to_tile(cursor_position))
+    {
+        gizmos.rect_2d(
+            level_data.tile_center(column, row),
+            Vec2::splat(level_data.grid_size),
+            CURSOR_COLOR,
+        );
+        draw_tile(
+            &mut gizmos,
+            level_data,
+            column,
+            row,
+            editor.brush,
+            CURSOR_COLOR,
+        );
+    }
+}
+
+fn draw_tile(
+    gizmos: &mut Gizmos,
+    level_data: &LevelData,
+    column: usize,
+    row: usize,
+    tile: u32,
+    color: Srgba,
+) {
+    gizmos.linestrip_2d(
+        tile_shape(tile)
+            .iter()
+            .map(|&local| level_data.tile_to_world(column, row, local)),
+        color,
+    );
+}
+
+fn cursor_world_position(
+    window: &Window,
+    (camera, camera_transform): &(&Camera, &GlobalTransform),
+) -> Option<Vec2> {
+    let cursor_position = window.cursor_position()?;
+    camera
+        .viewport_to_world_2d(camera_transform, cursor_position)
+        .ok()
+}
+
+fn editor_help_text(brush: u32) -> String {
+    format!(
+        "EDITOR  brush: {brush}  (0-{MAX_TILE_ID} select, LMB paint, RMB erase, C/R generate caves/rooms, Ctrl+S save, E exit)"
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_level(path: &str, level_data: &LevelData) {
+    use crate::level::asset::LevelFileFormat;
+
+    let format = LevelFileFormat::from_path(path);
+    if format != LevelFileFormat::Json {
+        warn!("Saving {format:?} levels isn't supported, edit \"{path}\" by hand instead");
+        return;
+    }
+
+    match std::fs::write(path, level_data.to_json()) {
+        Ok(()) => info!("Saved level to \"{path}\""),
+        Err(err) => error!("Failed to save level to \"{path}\": {err}"),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_level(_path: &str, _level_data: &LevelData) {
+    warn!("Saving levels isn't supported on the web");
+}
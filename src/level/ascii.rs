@@ -0,0 +1,116 @@
+use bevy::math::Vec2;
+
+use super::{check_level, format::CreatureSpawn, LevelData, LevelError};
+
+/// Extension of ASCII-art levels
+pub const ASCII_LEVEL_EXTENSION: &str = "txt";
+
+/// Character marking the player spawn
+const PLAYER_CHARACTER: char = 'P';
+
+/// Parse an ASCII-art level, one character per tile
+///
+/// ```text
+/// ##########
+/// #   P    #
+/// #       /#
+/// #\  C  /##
+/// ##########
+/// ```
+///
+/// * `#` is a square, space or `.` is empty
+/// * `/` and `\` are slopes facing away from the neighbouring squares, preferring floors.
+///   E.g. `/` is a bottom right slope if there's a square below or to its right, and a top
+///   left slope if there's only a square above or to its left.
+/// * Digits are that tile ID, for spikes or slopes that face the other way
+/// * `P` is the player spawn and any other letter is a creature spawn named after the letter,
+///   both on an empty tile
+///
+/// Rows shorter than the longest row are padded with empty tiles, since editors often strip
+/// trailing spaces. Blank lines before and after the grid are ignored. Levels use the default
+/// grid size.
+pub fn parse_ascii_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    read_ascii_level(level_data).and_then(check_level)
+}
+
+/// Same as `parse_ascii_level`, without checking the tile grid
+pub fn read_ascii_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    let text = std::str::from_utf8(level_data).map_err(LevelError::InvalidUtf8)?;
+
+    let lines: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| line.trim_end().chars().collect())
+        .collect();
+    let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(0);
+    let last = lines
+        .iter()
+        .rposition(|line| !line.is_empty())
+        .map_or(0, |last| last + 1);
+    let mut lines = lines[first..last.max(first)].to_vec();
+
+    let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+    for line in &mut lines {
+        line.resize(width, ' ');
+    }
+
+    let is_square = |column: Option<usize>, row: Option<usize>| match (column, row) {
+        (Some(column), Some(row)) => lines
+            .get(row)
+            .and_then(|line| line.get(column))
+            .is_some_and(|&character| matches!(character, '#' | '1')),
+        _ => false,
+    };
+
+    let mut tiles = vec![vec![0; width]; lines.len()];
+    let mut spawns: Vec<(char, usize, usize)> = Vec::new();
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, &character) in line.iter().enumerate() {
+            let above = is_square(Some(column), row.checked_sub(1));
+            let below = is_square(Some(column), Some(row + 1));
+            let left = is_square(column.checked_sub(1), Some(row));
+            let right = is_square(Some(column + 1), Some(row));
+
+            tiles[row][column] = match character {
+                ' ' | '.' => 0,
+                '#' => 1,
+                // Bottom right, or top left under a ceiling
+                '/' if !(below || right) && (above || left) => 4,
+                '/' => 3,
+                // Bottom left, or top right under a ceiling
+                '\\' if !(below || left) && (above || right) => 5,
+                '\\' => 2,
+                '0'..='9' => character.to_digit(10).unwrap_or_default(),
+                _ if character.is_alphabetic() => {
+                    spawns.push((character, column, row));
+                    0
+                }
+                _ => {
+                    return Err(LevelError::UnknownCharacter {
+                        row,
+                        column,
+                        character,
+                    })
+                }
+            };
+        }
+    }
+
+    let mut level = LevelData::from_tiles(tiles);
+    level.player_spawn = Vec2::ZERO;
+    level.creature_spawns.clear();
+
+    for (character, column, row) in spawns {
+        let position = level.tile_center(column, row);
+        if character == PLAYER_CHARACTER {
+            level.player_spawn = position;
+        } else {
+            level.creature_spawns.push(CreatureSpawn {
+                name: character.to_string(),
+                position,
+            });
+        }
+    }
+
+    Ok(level)
+}
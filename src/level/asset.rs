@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use bevy::asset::{
+    io::{AssetSourceBuilder, Reader},
+    AssetLoader, AssetPath, LoadContext,
+};
+
+use super::{
+    ascii::{parse_ascii_level, read_ascii_level, ASCII_LEVEL_EXTENSION},
+    bake::{parse_baked_level, BakedLevel, BAKED_LEVEL_EXTENSION},
+    bitmap::{parse_bitmap_level, read_bitmap_level, BITMAP_LEVEL_EXTENSION},
+    format::{parse_level_data, read_level_data},
+    svg::{parse_svg_level, read_svg_level, SVG_EXTENSION},
+    tiled::{parse_tiled_map, read_tiled_map, TILED_MAP_EXTENSION},
+    LevelData, LevelError,
+};
+
+/// Name of the asset source serving the directory the level file lives in
+pub const LEVEL_ASSET_SOURCE: &str = "level";
+
+/// File formats levels can be loaded from, picked by file extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelFileFormat {
+    /// Level JSON files (v1 or v2), the only format the editor saves
+    Json,
+    /// Tiled JSON maps
+    Tiled,
+    /// ASCII-art levels
+    Ascii,
+    /// SVG drawings of free-form polygons
+    Svg,
+    /// PNG images, one pixel per tile
+    Bitmap,
+}
+
+impl LevelFileFormat {
+    /// Format of a level path, falling back to level JSON for unknown extensions
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some(TILED_MAP_EXTENSION) => LevelFileFormat::Tiled,
+            Some(ASCII_LEVEL_EXTENSION) => LevelFileFormat::Ascii,
+            Some(SVG_EXTENSION) => LevelFileFormat::Svg,
+            Some(BITMAP_LEVEL_EXTENSION) => LevelFileFormat::Bitmap,
+            _ => LevelFileFormat::Json,
+        }
+    }
+}
+
+/// Loads `LevelData` assets from any `LevelFileFormat`
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = LevelData;
+    type Settings = ();
+    type Error = LevelError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(LevelError::Io)?;
+
+        parse_level_file(load_context.path(), &bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[
+            "json",
+            TILED_MAP_EXTENSION,
+            ASCII_LEVEL_EXTENSION,
+            SVG_EXTENSION,
+            BITMAP_LEVEL_EXTENSION,
+        ]
+    }
+}
+
+/// Loads `BakedLevel` assets written by the `bake` subcommand
+#[derive(Default)]
+pub struct BakedLevelLoader;
+
+impl AssetLoader for BakedLevelLoader {
+    type Asset = BakedLevel;
+    type Settings = ();
+    type Error = LevelError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(LevelError::Io)?;
+
+        parse_baked_level(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[BAKED_LEVEL_EXTENSION]
+    }
+}
+
+/// Path of the baked geometry for the level at `level_path`
+pub fn baked_level_path(level_path: &str) -> String {
+    format!("{level_path}.{BAKED_LEVEL_EXTENSION}")
+}
+
+/// Parse the contents of a level file in the format its path's extension names
+pub fn parse_level_file(path: impl AsRef<Path>, bytes: &[u8]) -> Result<LevelData, LevelError> {
+    match LevelFileFormat::from_path(path) {
+        LevelFileFormat::Json => parse_level_data(bytes),
+        LevelFileFormat::Tiled => parse_tiled_map(bytes),
+        LevelFileFormat::Ascii => parse_ascii_level(bytes),
+        LevelFileFormat::Svg => parse_svg_level(bytes),
+        LevelFileFormat::Bitmap => parse_bitmap_level(bytes),
+    }
+}
+
+/// Same as `parse_level_file`, without checking the tile grid or free-form polygons
+///
+/// For reporting every problem with `validate_level` instead of the first.
+pub fn read_level_file(path: impl AsRef<Path>, bytes: &[u8]) -> Result<LevelData, LevelError> {
+    match LevelFileFormat::from_path(path) {
+        LevelFileFormat::Json => read_level_data(bytes),
+        LevelFileFormat::Tiled => read_tiled_map(bytes),
+        LevelFileFormat::Ascii => read_ascii_level(bytes),
+        LevelFileFormat::Svg => read_svg_level(bytes),
+        LevelFileFormat::Bitmap => read_bitmap_level(bytes),
+    }
+}
+
+/// Asset source rooted at the directory containing `level_path`
+///
+/// Levels can live anywhere on disk, not just in `assets/`, so they get their own source.
+/// With the `file_watcher` feature the directory is watched and edits hot reload the level.
+/// Must be registered before `AssetPlugin`.
+pub fn level_asset_source(level_path: &str) -> AssetSourceBuilder {
+    let directory = Path::new(level_path)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    // Bevy resolves relative source paths against the executable/manifest directory,
+    // so anchor them to the working directory the path was given relative to
+    #[cfg(not(target_arch = "wasm32"))]
+    let directory = std::env::current_dir()
+        .map(|current_dir| current_dir.join(directory))
+        .unwrap_or_else(|_| directory.to_path_buf());
+
+    AssetSourceBuilder::platform_default(&directory.to_string_lossy(), None)
+}
+
+/// Asset path of `level_path` within the level asset source
+pub fn level_asset_path(level_path: &str) -> AssetPath<'static> {
+    let file_name = Path::new(level_path)
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    AssetPath::from(file_name).with_source(LEVEL_ASSET_SOURCE)
+}
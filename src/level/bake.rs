@@ -0,0 +1,207 @@
+use bevy::{
+    asset::Asset,
+    color::{Color, ColorToComponents, Srgba},
+    math::Vec2,
+    reflect::TypePath,
+};
+
+use super::{generate_level_polygons, LevelData, LevelError, Polygon};
+use crate::spatial::{EdgeId, SpatialGrid};
+
+/// Extension added to a level's path for its baked geometry, e.g. `level.json.bake`
+pub const BAKED_LEVEL_EXTENSION: &str = "bake";
+
+const BAKED_LEVEL_MAGIC: &[u8; 8] = b"FLEEBAKE";
+/// Bumped whenever the blob layout or the assignment of edges to grid cells changes
+const BAKED_LEVEL_VERSION: u32 = 2;
+/// Bumped whenever polygon extraction or the spatial grid changes the geometry a level
+/// produces, so levels baked by older code are extracted again
+const GEOMETRY_VERSION: u32 = 1;
+
+/// Level geometry extracted ahead of time, so loading skips `generate_level_polygons`
+///
+/// Blob layout, all little endian:
+/// * magic `FLEEBAKE`, version (u32), hash of the level it was baked from (u64)
+/// * level size and half size in tiles (2 x f32 each)
+/// * polygon count (u32), then per polygon its collision side (f32), sRGBA colour (4 x f32),
+///   point count (u32) and points (2 x f32 each)
+/// * spatial grid cell size (f32) and cell count (u32), then per cell its coordinates
+///   (2 x i32), edge count (u32) and edge IDs (u32 each)
+///
+/// Edge IDs count every polygon's edges in order, so edges aren't stored twice.
+#[derive(Asset, TypePath)]
+pub struct BakedLevel {
+    pub source_hash: u64,
+    pub polygons: Vec<Polygon>,
+    pub size: Vec2,
+    pub half_size: Vec2,
+    pub cell_size: f32,
+    pub cells: Vec<((i32, i32), Vec<EdgeId>)>,
+}
+
+impl BakedLevel {
+    /// Whether this was baked from `level_data` by this version of the game
+    pub fn is_baked_from(&self, level_data: &LevelData) -> bool {
+        self.source_hash == level_hash(level_data)
+    }
+
+    /// Rebuild the spatial grid from the baked cells
+    pub fn spatial_grid(&self) -> SpatialGrid {
+        let edges = SpatialGrid::polygon_edges(&self.polygons);
+        SpatialGrid::from_cells(self.cell_size, edges, self.cells.iter().cloned())
+    }
+}
+
+/// Hash of everything that affects a level's geometry
+///
+/// Covers the level's JSON, the game version, the blob version and `GEOMETRY_VERSION`, which
+/// stands in for the extraction code. Uses FNV-1a, which is stable between builds unlike
+/// `DefaultHasher`.
+pub fn level_hash(level_data: &LevelData) -> u64 {
+    let text = format!(
+        "{}\n{BAKED_LEVEL_VERSION}\n{GEOMETRY_VERSION}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        level_data.to_json()
+    );
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Extract a level's polygons and spatial grid and write them to a blob
+pub fn bake_level(level_data: &LevelData) -> Result<Vec<u8>, LevelError> {
+    let (polygons, size, half_size) = generate_level_polygons(level_data)?;
+    let spatial_grid = SpatialGrid::new(&polygons, level_data.grid_size);
+
+    let mut blob = BAKED_LEVEL_MAGIC.to_vec();
+    blob.extend(BAKED_LEVEL_VERSION.to_le_bytes());
+    blob.extend(level_hash(level_data).to_le_bytes());
+    write_floats(&mut blob, &[size.x, size.y, half_size.x, half_size.y]);
+
+    blob.extend((polygons.len() as u32).to_le_bytes());
+    for polygon in &polygons {
+        write_floats(&mut blob, &[polygon.collision_side]);
+        write_floats(&mut blob, &polygon.color.to_srgba().to_f32_array());
+        blob.extend((polygon.points.len() as u32).to_le_bytes());
+        for point in &polygon.points {
+            write_floats(&mut blob, &point.to_array());
+        }
+    }
+
+    // Sorted so baking the same level always gives the same blob
+    let mut cells: Vec<((i32, i32), &[EdgeId])> = spatial_grid.cells().collect();
+    cells.sort_by_key(|(cell, _)| *cell);
+
+    write_floats(&mut blob, &[spatial_grid.cell_size()]);
+    blob.extend((cells.len() as u32).to_le_bytes());
+    for ((x, y), edge_ids) in cells {
+        blob.extend(x.to_le_bytes());
+        blob.extend(y.to_le_bytes());
+        blob.extend((edge_ids.len() as u32).to_le_bytes());
+        for edge_id in edge_ids {
+            blob.extend(edge_id.to_le_bytes());
+        }
+    }
+
+    Ok(blob)
+}
+
+/// Read a blob written by `bake_level`
+pub fn parse_baked_level(blob: &[u8]) -> Result<BakedLevel, LevelError> {
+    let mut reader = BlobReader { blob, position: 0 };
+
+    if reader.bytes(BAKED_LEVEL_MAGIC.len())? != BAKED_LEVEL_MAGIC {
+        return Err(LevelError::InvalidBake("not a baked level".to_string()));
+    }
+    let version = reader.u32()?;
+    if version != BAKED_LEVEL_VERSION {
+        return Err(LevelError::InvalidBake(format!(
+            "unsupported version {version}, bake the level again"
+        )));
+    }
+    let source_hash = u64::from_le_bytes(reader.array()?);
+    let size = Vec2::new(reader.f32()?, reader.f32()?);
+    let half_size = Vec2::new(reader.f32()?, reader.f32()?);
+
+    let polygon_count = reader.u32()?;
+    let mut polygons = Vec::new();
+    for _ in 0..polygon_count {
+        let collision_side = reader.f32()?;
+        let color = Srgba::new(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?);
+        let point_count = reader.u32()?;
+        let points = (0..point_count)
+            .map(|_| Ok(Vec2::new(reader.f32()?, reader.f32()?)))
+            .collect::<Result<_, LevelError>>()?;
+
+        polygons.push(Polygon {
+            points,
+            collision_side,
+            color: Color::Srgba(color),
+        });
+    }
+
+    let cell_size = reader.f32()?;
+    let cell_count = reader.u32()?;
+    let mut cells = Vec::new();
+    for _ in 0..cell_count {
+        let cell = (reader.i32()?, reader.i32()?);
+        let edge_count = reader.u32()?;
+        let edge_ids = (0..edge_count)
+            .map(|_| reader.u32())
+            .collect::<Result<_, _>>()?;
+        cells.push((cell, edge_ids));
+    }
+
+    if reader.position != blob.len() {
+        return Err(LevelError::InvalidBake(
+            "unexpected data after the spatial grid".to_string(),
+        ));
+    }
+
+    Ok(BakedLevel {
+        source_hash,
+        polygons,
+        size,
+        half_size,
+        cell_size,
+        cells,
+    })
+}
+
+fn write_floats(blob: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        blob.extend(value.to_le_bytes());
+    }
+}
+
+struct BlobReader<'a> {
+    blob: &'a [u8],
+    position: usize,
+}
+
+impl BlobReader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], LevelError> {
+        let bytes = self
+            .blob
+            .get(self.position..self.position + count)
+            .ok_or_else(|| LevelError::InvalidBake("unexpected end of data".to_string()))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LevelError> {
+        Ok(self.bytes(N)?.try_into().expect("read N bytes"))
+    }
+
+    fn u32(&mut self) -> Result<u32, LevelError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, LevelError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, LevelError> {
+        self.array().map(f32::from_le_bytes)
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::color::Srgba;
+
+use super::{check_level, format::CreatureSpawn, LevelData, LevelError};
+
+/// Extension of bitmap levels
+pub const BITMAP_LEVEL_EXTENSION: &str = "png";
+
+/// Pixel colours of tiles, only exact matches count
+const TILE_COLORS: [([u8; 3], u32); 6] = [
+    ([0xff, 0xff, 0xff], 0),
+    ([0x00, 0x00, 0x00], 1),
+    ([0xff, 0x00, 0x00], 2),
+    ([0x00, 0xff, 0x00], 3),
+    ([0x00, 0x00, 0xff], 4),
+    ([0xff, 0x00, 0xff], 5),
+];
+const PLAYER_COLOR: [u8; 3] = [0x00, 0xff, 0xff];
+const CREATURE_COLOR: [u8; 3] = [0xff, 0xff, 0x00];
+
+/// Pixels less opaque than this are empty
+const MIN_ALPHA: u8 = 128;
+
+/// Parse a PNG level, one pixel per tile
+///
+/// * White or transparent pixels are empty and black pixels are squares
+/// * Red, green, blue and magenta (`#ff0000`, `#00ff00`, `#0000ff`, `#ff00ff`) are the bottom
+///   left, bottom right, top left and top right slopes (tile IDs 2-5)
+/// * A cyan (`#00ffff`) pixel is the player spawn and yellow (`#ffff00`) pixels are creature
+///   spawns, both on an empty tile. Creatures are numbered in reading order.
+///
+/// Any other colour is an error, so layouts have to be drawn without anti-aliasing. Levels use
+/// the default grid size.
+pub fn parse_bitmap_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    read_bitmap_level(level_data).and_then(check_level)
+}
+
+/// Same as `parse_bitmap_level`, without checking the tile grid
+pub fn read_bitmap_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(level_data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| LevelError::InvalidPng(err.to_string()))?;
+
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader
+        .next_frame(&mut pixels)
+        .map_err(|err| LevelError::InvalidPng(err.to_string()))?;
+
+    let channels = info.color_type.samples();
+    let rgba = |pixel: &[u8]| match info.color_type {
+        png::ColorType::Grayscale => [pixel[0], pixel[0], pixel[0], u8::MAX],
+        png::ColorType::GrayscaleAlpha => [pixel[0], pixel[0], pixel[0], pixel[1]],
+        png::ColorType::Rgb => [pixel[0], pixel[1], pixel[2], u8::MAX],
+        _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+    };
+
+    let mut tiles = vec![vec![0; info.width as usize]; info.height as usize];
+    let mut spawns = Vec::new();
+
+    for (row, line) in pixels
+        .chunks_exact(info.line_size)
+        .take(info.height as usize)
+        .enumerate()
+    {
+        for (column, pixel) in line.chunks_exact(channels).enumerate() {
+            let [red, green, blue, alpha] = rgba(pixel);
+            if alpha < MIN_ALPHA {
+                continue;
+            }
+
+            let color = [red, green, blue];
+            tiles[row][column] = match TILE_COLORS
+                .iter()
+                .find(|(tile_color, _)| *tile_color == color)
+            {
+                Some(&(_, tile)) => tile,
+                None if color == PLAYER_COLOR || color == CREATURE_COLOR => {
+                    spawns.push((color, column, row));
+                    0
+                }
+                None => {
+                    return Err(LevelError::UnknownPixelColor {
+                        row,
+                        column,
+                        color: Srgba::rgb_u8(red, green, blue),
+                    })
+                }
+            };
+        }
+    }
+
+    let mut level = LevelData::from_tiles(tiles);
+    level.creature_spawns.clear();
+
+    for (color, column, row) in spawns {
+        let position = level.tile_center(column, row);
+        if color == PLAYER_COLOR {
+            level.player_spawn = position;
+        } else {
+            level.creature_spawns.push(CreatureSpawn {
+                name: format!("creature_{}", level.creature_spawns.len()),
+                position,
+            });
+        }
+    }
+
+    Ok(level)
+}
@@ -0,0 +1,260 @@
+use bevy::{
+    color::Color,
+    math::{Rect, Vec2},
+};
+
+use super::{calculate_winding_order, polygon_nesting, Polygon};
+use crate::utils::cross_product;
+
+/// Triangles covering one solid area of the level, in the colour of the polygon around it
+pub struct WallFill {
+    pub color: Color,
+    pub triangles: Vec<[Vec2; 3]>,
+}
+
+/// Triangulate the solid areas of the level
+///
+/// A polygon that's solid inside is filled, minus the open polygons nested directly in it.
+/// Outermost polygons that are open inside (cave walls) are solid outside, so `bounds` is
+/// filled around them, grown to fit every polygon.
+pub fn wall_fills(polygons: &[Polygon], bounds: Rect) -> Vec<WallFill> {
+    let points: Vec<Vec<Vec2>> = polygons
+        .iter()
+        .map(|polygon| polygon.points.clone())
+        .collect();
+    let nesting = polygon_nesting(&points);
+    // Polygons collide on their winding side when they're solid inside
+    let solid_inside: Vec<bool> = polygons
+        .iter()
+        .map(|polygon| polygon.collision_side * calculate_winding_order(&polygon.points) > 0.0)
+        .collect();
+    let open_loop = |i: usize| points[i][..points[i].len() - 1].to_vec();
+
+    let mut fills: Vec<WallFill> = (0..polygons.len())
+        .filter(|&i| solid_inside[i])
+        .map(|i| {
+            let holes: Vec<Vec<Vec2>> = (0..polygons.len())
+                .filter(|&j| nesting[j].parent == Some(i) && !solid_inside[j])
+                .map(open_loop)
+                .collect();
+
+            WallFill {
+                color: polygons[i].color,
+                triangles: triangulate(&open_loop(i), &holes),
+            }
+        })
+        .collect();
+
+    let outer_holes: Vec<usize> = (0..polygons.len())
+        .filter(|&i| !solid_inside[i] && nesting[i].parent.is_none())
+        .collect();
+    if let Some(&first) = outer_holes.first() {
+        let bounds = points
+            .iter()
+            .flatten()
+            .fold(bounds, |bounds, &point| bounds.union_point(point));
+        let outline = [
+            bounds.min,
+            Vec2::new(bounds.max.x, bounds.min.y),
+            bounds.max,
+            Vec2::new(bounds.min.x, bounds.max.y),
+        ];
+        let holes: Vec<Vec<Vec2>> = outer_holes.into_iter().map(open_loop).collect();
+
+        fills.push(WallFill {
+            color: polygons[first].color,
+            triangles: triangulate(&outline, &holes),
+        });
+    }
+
+    fills
+}
+
+/// Triangulate a simple polygon with holes by ear clipping
+///
+/// Loops are open (the first point isn't repeated) and can be wound either way. Each hole is
+/// joined to the outline by a bridge to a vertex it can see, turning the polygon into a single
+/// loop that touches itself along the bridges.
+pub fn triangulate(outline: &[Vec2], holes: &[Vec<Vec2>]) -> Vec<[Vec2; 3]> {
+    // Counter-clockwise outline and clockwise holes keep the solid area on the left
+    let mut ring = outline.to_vec();
+    if signed_area(&ring) < 0.0 {
+        ring.reverse();
+    }
+    let mut holes: Vec<Vec<Vec2>> = holes
+        .iter()
+        .filter(|hole| hole.len() >= 3)
+        .map(|hole| {
+            let mut hole = hole.clone();
+            if signed_area(&hole) > 0.0 {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect();
+
+    // Bridge the rightmost holes first, so their bridges don't block the holes to their left
+    let max_x = |hole: &Vec<Vec2>| hole.iter().map(|point| point.x).fold(f32::MIN, f32::max);
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+    for i in 0..holes.len() {
+        let (hole, rest) = holes[i..].split_first().expect("index in bounds");
+        ring = bridge_hole(&ring, hole, rest);
+    }
+
+    clip_ears(&ring)
+}
+
+/// Join a hole to the ring through the nearest ring vertex the hole's rightmost vertex can see
+fn bridge_hole(ring: &[Vec2], hole: &[Vec2], other_holes: &[Vec<Vec2>]) -> Vec<Vec2> {
+    let start = (0..hole.len())
+        .max_by(|&a, &b| hole[a].x.total_cmp(&hole[b].x))
+        .expect("holes have points");
+    let from = hole[start];
+
+    let edges = |points: &[Vec2]| {
+        (0..points.len())
+            .map(|i| (points[i], points[(i + 1) % points.len()]))
+            .collect::<Vec<_>>()
+    };
+    let mut blockers = edges(ring);
+    blockers.extend(edges(hole));
+    for other in other_holes {
+        blockers.extend(edges(other));
+    }
+
+    let mut candidates: Vec<usize> = (0..ring.len()).collect();
+    candidates.sort_by(|&a, &b| {
+        ring[a]
+            .distance_squared(from)
+            .total_cmp(&ring[b].distance_squared(from))
+    });
+    let hole_prev = hole[(start + hole.len() - 1) % hole.len()];
+    let hole_next = hole[(start + 1) % hole.len()];
+    let Some(target) = candidates.into_iter().find(|&i| {
+        let to = ring[i];
+        let prev = ring[(i + ring.len() - 1) % ring.len()];
+        let next = ring[(i + 1) % ring.len()];
+
+        to != from
+            && locally_inside(prev, to, next, from)
+            && locally_inside(hole_prev, from, hole_next, to)
+            && !blockers
+                .iter()
+                .any(|&(a, b)| segment_blocks(from, to, a, b))
+    }) else {
+        return ring.to_vec();
+    };
+
+    // Ring up to the target, round the hole and back to the target
+    let mut bridged = ring[..=target].to_vec();
+    bridged.extend(hole[start..].iter().chain(&hole[..=start]));
+    bridged.extend(&ring[target..]);
+    bridged
+}
+
+/// Whether the direction from `point` to `toward` starts out inside the polygon, given the
+/// vertices either side of `point` with the solid area on the left
+fn locally_inside(prev: Vec2, point: Vec2, next: Vec2, toward: Vec2) -> bool {
+    let direction = toward - point;
+    let (to_prev, to_next) = (prev - point, next - point);
+
+    if cross_product(point - prev, next - point) >= 0.0 {
+        // Convex, inside the wedge from next round to prev
+        cross_product(to_next, direction) > 0.0 && cross_product(direction, to_prev) > 0.0
+    } else {
+        // Reflex, anywhere but the wedge from prev round to next
+        !(cross_product(to_prev, direction) >= 0.0 && cross_product(direction, to_next) >= 0.0)
+    }
+}
+
+/// Whether the edge `a`-`b` crosses the bridge `from`-`to`, or touches it between its ends
+fn segment_blocks(from: Vec2, to: Vec2, a: Vec2, b: Vec2) -> bool {
+    if a == from || a == to || b == from || b == to {
+        return false;
+    }
+
+    let side_a = cross_product(to - from, a - from);
+    let side_b = cross_product(to - from, b - from);
+    let side_from = cross_product(b - a, from - a);
+    let side_to = cross_product(b - a, to - a);
+    if side_a * side_b < 0.0 && side_from * side_to < 0.0 {
+        return true;
+    }
+
+    // An edge end lying on the bridge
+    let on_bridge = |point: Vec2, side: f32| {
+        side == 0.0 && (point - from).dot(to - from) > 0.0 && (point - to).dot(from - to) > 0.0
+    };
+    on_bridge(a, side_a) || on_bridge(b, side_b)
+}
+
+/// Triangulate a counter-clockwise loop by repeatedly cutting off convex corners
+fn clip_ears(ring: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2));
+    let mut prev: Vec<usize> = (0..ring.len())
+        .map(|i| (i + ring.len() - 1) % ring.len())
+        .collect();
+    let mut next: Vec<usize> = (0..ring.len()).map(|i| (i + 1) % ring.len()).collect();
+
+    let mut remaining = ring.len();
+    let mut current = 0;
+    // Vertices visited since the last clip, a full lap without one means the loop is broken
+    let mut stalled = 0;
+
+    while remaining > 2 && stalled < remaining {
+        let (a, b, c) = (prev[current], current, next[current]);
+        let turn = cross_product(ring[b] - ring[a], ring[c] - ring[b]);
+
+        let clip = if turn == 0.0 {
+            // A straight run or a spike with no area, drop the vertex
+            true
+        } else if turn > 0.0 && is_ear(ring, &next, a, b, c) {
+            triangles.push([ring[a], ring[b], ring[c]]);
+            true
+        } else {
+            false
+        };
+
+        if clip {
+            next[a] = c;
+            prev[c] = a;
+            remaining -= 1;
+            stalled = 0;
+            current = a;
+        } else {
+            stalled += 1;
+            current = c;
+        }
+    }
+
+    triangles
+}
+
+/// Whether no other vertex of the loop is inside or on the triangle `a`-`b`-`c`
+fn is_ear(ring: &[Vec2], next: &[usize], a: usize, b: usize, c: usize) -> bool {
+    let (pa, pb, pc) = (ring[a], ring[b], ring[c]);
+
+    let mut i = next[c];
+    while i != a {
+        let point = ring[i];
+        let outside_corner = point != pa && point != pb && point != pc;
+        if outside_corner
+            && cross_product(pb - pa, point - pa) >= 0.0
+            && cross_product(pc - pb, point - pb) >= 0.0
+            && cross_product(pa - pc, point - pc) >= 0.0
+        {
+            return false;
+        }
+        i = next[i];
+    }
+
+    true
+}
+
+/// Shoelace area, positive for counter-clockwise loops
+fn signed_area(points: &[Vec2]) -> f32 {
+    (0..points.len())
+        .map(|i| cross_product(points[i], points[(i + 1) % points.len()]))
+        .sum::<f32>()
+        / 2.0
+}
@@ -0,0 +1,328 @@
+use bevy::{
+    asset::Asset,
+    color::{Color, Srgba},
+    math::{Rect, Vec2},
+    reflect::TypePath,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    check_level,
+    shapes::{covering_grid_size, CollisionSide, LevelPolygon},
+    theme::{LevelTheme, ThemeName},
+    LevelError,
+};
+
+/// Grid size used by v1 levels and v2 levels that don't specify one
+pub const DEFAULT_GRID_SIZE: f32 = 32.0;
+
+/// Player spawn used by v1 levels
+pub const DEFAULT_PLAYER_SPAWN: Vec2 = Vec2::ZERO;
+
+/// Creature spawn used by v1 levels
+pub const DEFAULT_CREATURE_SPAWN: Vec2 = Vec2::new(100.0, 100.0);
+
+/// Newest level format version
+pub const LEVEL_FORMAT_VERSION: u64 = 2;
+
+/// A named spawn point for a creature
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatureSpawn {
+    pub name: String,
+    pub position: Vec2,
+}
+
+/// A named rectangular area, e.g. a goal or checkpoint
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriggerArea {
+    pub name: String,
+    pub rect: Rect,
+}
+
+/// Everything loaded from a level file
+///
+/// Spawn positions and trigger areas are in world space (pixels, origin at the level centre, Y up).
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct LevelData {
+    pub tiles: Vec<Vec<u32>>,
+    pub grid_size: f32,
+    pub player_spawn: Vec2,
+    pub creature_spawns: Vec<CreatureSpawn>,
+    /// Built-in theme the level is drawn with
+    pub theme: ThemeName,
+    /// Polygon colours replacing the theme's palette, the first is used for the outer container
+    pub palette: Vec<Color>,
+    /// Seed for picking polygon colours, so a level always looks the same
+    pub color_seed: u64,
+    pub triggers: Vec<TriggerArea>,
+    /// Free-form walls, added to the walls built from the tiles
+    pub polygons: Vec<LevelPolygon>,
+}
+
+impl LevelData {
+    /// Wrap a bare tile grid with the v1 defaults
+    pub fn from_tiles(tiles: Vec<Vec<u32>>) -> Self {
+        Self {
+            tiles,
+            grid_size: DEFAULT_GRID_SIZE,
+            player_spawn: DEFAULT_PLAYER_SPAWN,
+            creature_spawns: vec![CreatureSpawn {
+                name: "flee_ai".to_string(),
+                position: DEFAULT_CREATURE_SPAWN,
+            }],
+            theme: ThemeName::default(),
+            palette: Vec::new(),
+            color_seed: 0,
+            triggers: Vec::new(),
+            polygons: Vec::new(),
+        }
+    }
+
+    /// The level's theme, with its own palette if it has one
+    pub fn theme(&self) -> LevelTheme {
+        let mut theme = LevelTheme::builtin(self.theme);
+        if !self.palette.is_empty() {
+            theme.palette = self.palette.clone();
+        }
+        theme
+    }
+
+    /// Serialize to the v2 level format
+    pub fn to_json(&self) -> String {
+        let file = LevelFileV2 {
+            version: LEVEL_FORMAT_VERSION,
+            grid_size: self.grid_size,
+            spawns: SpawnsFile {
+                player: self.player_spawn.to_array(),
+                creatures: self
+                    .creature_spawns
+                    .iter()
+                    .map(|spawn| CreatureSpawnFile {
+                        name: spawn.name.clone(),
+                        position: spawn.position.to_array(),
+                    })
+                    .collect(),
+            },
+            theme: self.theme,
+            palette: self
+                .palette
+                .iter()
+                .map(|color| color.to_srgba().to_hex())
+                .collect(),
+            color_seed: self.color_seed,
+            triggers: self
+                .triggers
+                .iter()
+                .map(|trigger| TriggerAreaFile {
+                    name: trigger.name.clone(),
+                    min: trigger.rect.min.to_array(),
+                    max: trigger.rect.max.to_array(),
+                })
+                .collect(),
+            polygons: self
+                .polygons
+                .iter()
+                .map(|polygon| LevelPolygonFile {
+                    points: polygon
+                        .points
+                        .iter()
+                        .map(|point| point.to_array())
+                        .collect(),
+                    collision_side: polygon.collision_side,
+                    color: polygon.color.map(|color| color.to_srgba().to_hex()),
+                })
+                .collect(),
+            tiles: self.tiles.clone(),
+        };
+
+        let mut json =
+            serde_json::to_string_pretty(&file).expect("level files only hold JSON types");
+        json.push('\n');
+        json
+    }
+}
+
+/// On-disk layout of a v2 level
+///
+/// ```json
+/// {
+///   "version": 2,
+///   "grid_size": 32,
+///   "tiles": [[1, 1, 1], [1, 0, 1], [1, 1, 1]],
+///   "spawns": {
+///     "player": [0, 0],
+///     "creatures": [{ "name": "flee_ai", "position": [100, 100] }]
+///   },
+///   "theme": "color-blind",
+///   "palette": ["#3080ff", "#ffaa00"],
+///   "color_seed": 7,
+///   "triggers": [{ "name": "exit", "min": [-16, 200], "max": [16, 232] }],
+///   "polygons": [
+///     { "points": [[-40, 0], [0, 30], [40, 0], [-40, 0]], "collision_side": "outside", "color": "#808080" }
+///   ]
+/// }
+/// ```
+///
+/// `tiles` can be left out of levels made of `polygons` only, an empty grid covering the
+/// polygons is used instead so the level still has a size.
+#[derive(Serialize, Deserialize)]
+struct LevelFileV2 {
+    version: u64,
+    #[serde(default = "default_grid_size")]
+    grid_size: f32,
+    #[serde(default)]
+    spawns: SpawnsFile,
+    #[serde(default, skip_serializing_if = "is_default")]
+    theme: ThemeName,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    palette: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    color_seed: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    triggers: Vec<TriggerAreaFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    polygons: Vec<LevelPolygonFile>,
+    /// Last, so the small fields stay at the top of saved files
+    #[serde(default)]
+    tiles: Vec<Vec<u32>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SpawnsFile {
+    #[serde(default)]
+    player: [f32; 2],
+    #[serde(default)]
+    creatures: Vec<CreatureSpawnFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CreatureSpawnFile {
+    name: String,
+    position: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerAreaFile {
+    name: String,
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+struct LevelPolygonFile {
+    points: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collision_side: Option<CollisionSide>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+}
+
+fn default_grid_size() -> f32 {
+    DEFAULT_GRID_SIZE
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Parse level data in either format
+///
+/// * v1 - a bare `[[u32]]` tile grid, using the default grid size and spawns
+/// * v2 - an object with a `version` field, see `LevelFileV2`
+pub fn parse_level_data(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    read_level_data(level_data).and_then(check_level)
+}
+
+/// Same as `parse_level_data`, without checking the tile grid or free-form polygons
+pub fn read_level_data(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    let json_str = std::str::from_utf8(level_data).map_err(LevelError::InvalidUtf8)?;
+    let value: serde_json::Value =
+        serde_json::from_str(json_str).map_err(LevelError::InvalidJson)?;
+
+    let level = if value.is_array() {
+        let tiles: Vec<Vec<u32>> =
+            serde_json::from_value(value).map_err(LevelError::InvalidJson)?;
+        LevelData::from_tiles(tiles)
+    } else {
+        match value.get("version").and_then(serde_json::Value::as_u64) {
+            Some(LEVEL_FORMAT_VERSION) => {
+                let file: LevelFileV2 =
+                    serde_json::from_value(value).map_err(LevelError::InvalidJson)?;
+                parse_v2(file)?
+            }
+            Some(version) => return Err(LevelError::UnsupportedVersion(version)),
+            None => {
+                // Let serde report the missing/invalid field
+                let file: LevelFileV2 =
+                    serde_json::from_value(value).map_err(LevelError::InvalidJson)?;
+                return Err(LevelError::UnsupportedVersion(file.version));
+            }
+        }
+    };
+
+    Ok(level)
+}
+
+fn parse_v2(file: LevelFileV2) -> Result<LevelData, LevelError> {
+    if file.grid_size.is_nan() || file.grid_size <= 0.0 {
+        return Err(LevelError::InvalidGridSize(file.grid_size));
+    }
+
+    let palette = file
+        .palette
+        .into_iter()
+        .map(parse_color)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let polygons = file
+        .polygons
+        .into_iter()
+        .map(|polygon| {
+            Ok(LevelPolygon {
+                points: polygon.points.into_iter().map(Vec2::from).collect(),
+                collision_side: polygon.collision_side,
+                color: polygon.color.map(parse_color).transpose()?,
+            })
+        })
+        .collect::<Result<Vec<_>, LevelError>>()?;
+
+    let tiles = if file.tiles.is_empty() && !polygons.is_empty() {
+        let (columns, rows) = covering_grid_size(&polygons, file.grid_size);
+        vec![vec![0; columns]; rows]
+    } else {
+        file.tiles
+    };
+
+    Ok(LevelData {
+        tiles,
+        grid_size: file.grid_size,
+        player_spawn: Vec2::from(file.spawns.player),
+        creature_spawns: file
+            .spawns
+            .creatures
+            .into_iter()
+            .map(|spawn| CreatureSpawn {
+                name: spawn.name,
+                position: Vec2::from(spawn.position),
+            })
+            .collect(),
+        theme: file.theme,
+        palette,
+        color_seed: file.color_seed,
+        triggers: file
+            .triggers
+            .into_iter()
+            .map(|trigger| TriggerArea {
+                name: trigger.name,
+                rect: Rect::from_corners(Vec2::from(trigger.min), Vec2::from(trigger.max)),
+            })
+            .collect(),
+        polygons,
+    })
+}
+
+fn parse_color(hex: String) -> Result<Color, LevelError> {
+    Srgba::hex(&hex)
+        .map(Color::Srgba)
+        .map_err(|_| LevelError::InvalidColor(hex))
+}
@@ -0,0 +1,388 @@
+use std::collections::VecDeque;
+
+use bevy::math::Vec2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::LevelData;
+
+// Cave generation parameters
+const CAVE_FILL_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_STEPS: usize = 5;
+/// A tile becomes solid with more than this many solid neighbours and empty with fewer
+const CAVE_SOLID_NEIGHBOURS: usize = 4;
+
+/// Smallest width and height generated, a solid border around at least one open tile
+const MIN_LEVEL_SIZE: usize = 3;
+/// Size of the square cleared in the middle of a level that generated with no open tiles
+const MIN_OPEN_SIZE: usize = 3;
+
+// Room generation parameters
+const MIN_ROOM_SIZE: usize = 3;
+/// BSP leaves are at least big enough for the smallest room plus a wall on each side
+const MIN_LEAF_SIZE: usize = MIN_ROOM_SIZE + 3;
+const CORRIDOR_WIDTH: usize = 2;
+
+/// Kind of layout to generate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeneratorKind {
+    /// Organic caves from cellular automata smoothing of random noise
+    Caves,
+    /// Rectangular rooms from binary space partitioning, joined by corridors
+    Rooms,
+}
+
+/// Generate a tile grid for the polygon extraction
+///
+/// The same kind, size and seed always produce the same grid. The outer border is always
+/// solid, there is at least one empty tile, every empty tile is reachable from every other
+/// one, and outward facing corners
+/// are replaced with slope tiles 2-5. Sizes below 3x3 are grown to 3x3.
+pub fn generate_tiles(
+    kind: GeneratorKind,
+    width: usize,
+    height: usize,
+    seed: u64,
+) -> Vec<Vec<u32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (width, height) = (width.max(MIN_LEVEL_SIZE), height.max(MIN_LEVEL_SIZE));
+
+    let mut tiles = vec![vec![1; width]; height];
+    match kind {
+        GeneratorKind::Caves => carve_caves(&mut tiles, &mut rng),
+        GeneratorKind::Rooms => {
+            let interior = Rect {
+                x: 1,
+                y: 1,
+                width: width - 2,
+                height: height - 2,
+            };
+            carve_rooms(&mut tiles, interior, &mut rng);
+        }
+    }
+
+    open_diagonal_pinches(&mut tiles);
+    // Small or unlucky caves can fill in completely, so always leave somewhere to stand
+    if open_tiles(&tiles).next().is_none() {
+        let size = MIN_OPEN_SIZE.min(width - 2).min(height - 2);
+        carve_square(&mut tiles, (width - size) / 2, (height - size) / 2, size);
+    }
+    keep_largest_region(&mut tiles);
+    place_slopes(&mut tiles);
+
+    tiles
+}
+
+/// Generate a complete level, with the player near the centre and a creature as far away as possible
+pub fn generate_level(
+    kind: GeneratorKind,
+    width: usize,
+    height: usize,
+    seed: u64,
+    grid_size: f32,
+) -> LevelData {
+    let mut level = LevelData::from_tiles(generate_tiles(kind, width, height, seed));
+    level.grid_size = grid_size;
+
+    let open_positions: Vec<Vec2> = open_tiles(&level.tiles)
+        .map(|(x, y)| level.tile_center(x, y))
+        .collect();
+
+    if let Some(player_spawn) = level.nearest_open_position(Vec2::ZERO, &[]) {
+        level.player_spawn = player_spawn;
+    }
+    if let Some(creature_spawn) = open_positions.iter().copied().max_by(|a, b| {
+        a.distance_squared(level.player_spawn)
+            .total_cmp(&b.distance_squared(level.player_spawn))
+    }) {
+        for spawn in &mut level.creature_spawns {
+            spawn.position = creature_spawn;
+        }
+    }
+
+    level
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+fn carve_caves(tiles: &mut [Vec<u32>], rng: &mut StdRng) {
+    let (width, height) = (tiles[0].len(), tiles.len());
+
+    // Random noise inside a solid border
+    for row in &mut tiles[1..height - 1] {
+        for tile in &mut row[1..width - 1] {
+            *tile = u32::from(rng.random_bool(CAVE_FILL_CHANCE));
+        }
+    }
+
+    // Smooth the noise into caves
+    for _ in 0..CAVE_SMOOTHING_STEPS {
+        let previous = tiles.to_vec();
+        for (y, row) in tiles.iter_mut().enumerate().take(height - 1).skip(1) {
+            for (x, tile) in row.iter_mut().enumerate().take(width - 1).skip(1) {
+                let solid_neighbours = (y - 1..=y + 1)
+                    .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                    .filter(|&(nx, ny)| (nx, ny) != (x, y) && previous[ny][nx] != 0)
+                    .count();
+
+                if solid_neighbours > CAVE_SOLID_NEIGHBOURS {
+                    *tile = 1;
+                } else if solid_neighbours < CAVE_SOLID_NEIGHBOURS {
+                    *tile = 0;
+                }
+            }
+        }
+    }
+}
+
+/// Recursively split `rect` and carve a room into each leaf
+///
+/// Returns a tile inside one of the carved rooms so the caller can connect to it.
+fn carve_rooms(tiles: &mut [Vec<u32>], rect: Rect, rng: &mut StdRng) -> (usize, usize) {
+    let can_split_x = rect.width >= MIN_LEAF_SIZE * 2;
+    let can_split_y = rect.height >= MIN_LEAF_SIZE * 2;
+
+    if !can_split_x && !can_split_y {
+        // Leaf, carve a room with at least a one tile margin where the leaf allows it
+        let max_width = rect.width.saturating_sub(2).max(1);
+        let max_height = rect.height.saturating_sub(2).max(1);
+        let room_width = rng.random_range(MIN_ROOM_SIZE.min(max_width)..=max_width);
+        let room_height = rng.random_range(MIN_ROOM_SIZE.min(max_height)..=max_height);
+        let room = Rect {
+            x: rect.x + random_margin(rect.width - room_width, rng),
+            y: rect.y + random_margin(rect.height - room_height, rng),
+            width: room_width,
+            height: room_height,
+        };
+        for row in &mut tiles[room.y..room.y + room.height] {
+            row[room.x..room.x + room.width].fill(0);
+        }
+        return room.center();
+    }
+
+    // Split across the longer side when both are possible
+    let split_x = can_split_x && (!can_split_y || rect.width >= rect.height);
+    let (first, second) = if split_x {
+        let split = rng.random_range(MIN_LEAF_SIZE..=rect.width - MIN_LEAF_SIZE);
+        (
+            Rect {
+                width: split,
+                ..rect
+            },
+            Rect {
+                x: rect.x + split,
+                width: rect.width - split,
+                ..rect
+            },
+        )
+    } else {
+        let split = rng.random_range(MIN_LEAF_SIZE..=rect.height - MIN_LEAF_SIZE);
+        (
+            Rect {
+                height: split,
+                ..rect
+            },
+            Rect {
+                y: rect.y + split,
+                height: rect.height - split,
+                ..rect
+            },
+        )
+    };
+
+    let first_room = carve_rooms(tiles, first, rng);
+    let second_room = carve_rooms(tiles, second, rng);
+    carve_corridor(tiles, first_room, second_room, rng.random_bool(0.5));
+
+    if rng.random_bool(0.5) {
+        first_room
+    } else {
+        second_room
+    }
+}
+
+/// Offset of a room within `slack` spare tiles, keeping at least one tile either side if possible
+fn random_margin(slack: usize, rng: &mut StdRng) -> usize {
+    if slack >= 2 {
+        rng.random_range(1..slack)
+    } else {
+        0
+    }
+}
+
+/// Carve an L shaped corridor between two tiles
+fn carve_corridor(
+    tiles: &mut [Vec<u32>],
+    start: (usize, usize),
+    end: (usize, usize),
+    horizontal_first: bool,
+) {
+    let corner = if horizontal_first {
+        (end.0, start.1)
+    } else {
+        (start.0, end.1)
+    };
+
+    for (from, to) in [(start, corner), (corner, end)] {
+        for y in from.1.min(to.1)..=from.1.max(to.1) {
+            for x in from.0.min(to.0)..=from.0.max(to.0) {
+                carve_square(tiles, x, y, CORRIDOR_WIDTH);
+            }
+        }
+    }
+}
+
+/// Clear a square of tiles with its top left at (x, y), leaving the border solid
+fn carve_square(tiles: &mut [Vec<u32>], x: usize, y: usize, size: usize) {
+    let (width, height) = (tiles[0].len(), tiles.len());
+    for row in tiles.iter_mut().take((y + size).min(height - 1)).skip(y) {
+        for tile in row.iter_mut().take((x + size).min(width - 1)).skip(x) {
+            *tile = 0;
+        }
+    }
+}
+
+/// Open up 2x2 checkerboards of solid and empty tiles
+///
+/// Solid tiles that only touch at a corner share a vertex with four edges, which the
+/// polygon extraction can't separate, so the solid pair is cleared instead.
+fn open_diagonal_pinches(tiles: &mut [Vec<u32>]) {
+    let (width, height) = (tiles[0].len(), tiles.len());
+    let is_border = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for y in 0..height - 1 {
+            for x in 0..width - 1 {
+                let top_left = tiles[y][x] != 0;
+                let top_right = tiles[y][x + 1] != 0;
+                let bottom_left = tiles[y + 1][x] != 0;
+                let bottom_right = tiles[y + 1][x + 1] != 0;
+
+                if top_left != bottom_right || top_right != bottom_left || top_left == top_right {
+                    continue;
+                }
+
+                let solid_pair = if top_left {
+                    [(x, y), (x + 1, y + 1)]
+                } else {
+                    [(x + 1, y), (x, y + 1)]
+                };
+                for (tile_x, tile_y) in solid_pair {
+                    if !is_border(tile_x, tile_y) {
+                        tiles[tile_y][tile_x] = 0;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Fill every empty region except the largest, so all open space is connected
+fn keep_largest_region(tiles: &mut [Vec<u32>]) {
+    let (width, height) = (tiles[0].len(), tiles.len());
+    let mut region_ids = vec![vec![usize::MAX; width]; height];
+    let mut region_sizes = Vec::new();
+
+    for (x, y) in open_tiles(tiles).collect::<Vec<_>>() {
+        if region_ids[y][x] != usize::MAX {
+            continue;
+        }
+
+        // Flood fill the region
+        let region = region_sizes.len();
+        let mut size = 0;
+        let mut queue = VecDeque::from([(x, y)]);
+        region_ids[y][x] = region;
+        while let Some((tile_x, tile_y)) = queue.pop_front() {
+            size += 1;
+            let neighbours = [
+                (tile_x.wrapping_sub(1), tile_y),
+                (tile_x + 1, tile_y),
+                (tile_x, tile_y.wrapping_sub(1)),
+                (tile_x, tile_y + 1),
+            ];
+            for (nx, ny) in neighbours {
+                if nx < width
+                    && ny < height
+                    && tiles[ny][nx] == 0
+                    && region_ids[ny][nx] == usize::MAX
+                {
+                    region_ids[ny][nx] = region;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        region_sizes.push(size);
+    }
+
+    let Some(largest) = (0..region_sizes.len()).max_by_key(|&region| region_sizes[region]) else {
+        return;
+    };
+
+    for (row, row_ids) in tiles.iter_mut().zip(&region_ids) {
+        for (tile, &region) in row.iter_mut().zip(row_ids) {
+            if *tile == 0 && region != largest {
+                *tile = 1;
+            }
+        }
+    }
+}
+
+/// Turn solid tiles with two perpendicular empty neighbours into slopes facing the open space
+fn place_slopes(tiles: &mut [Vec<u32>]) {
+    let (width, height) = (tiles[0].len(), tiles.len());
+    let previous = tiles.to_vec();
+    // Outside the grid counts as solid
+    let is_empty = |x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) if x < width && y < height => previous[y][x] == 0,
+        _ => false,
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            if previous[y][x] != 1 {
+                continue;
+            }
+
+            let top = is_empty(Some(x), y.checked_sub(1));
+            let bottom = is_empty(Some(x), Some(y + 1));
+            let left = is_empty(x.checked_sub(1), Some(y));
+            let right = is_empty(Some(x + 1), Some(y));
+
+            tiles[y][x] = match (top, bottom, left, right) {
+                // Bottom left
+                (true, false, false, true) => 2,
+                // Bottom right
+                (true, false, true, false) => 3,
+                // Top left
+                (false, true, false, true) => 4,
+                // Top right
+                (false, true, true, false) => 5,
+                _ => 1,
+            };
+        }
+    }
+}
+
+fn open_tiles(tiles: &[Vec<u32>]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    tiles.iter().enumerate().flat_map(|(y, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, &tile)| tile == 0)
+            .map(move |(x, _)| (x, y))
+    })
+}
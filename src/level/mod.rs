@@ -0,0 +1,1078 @@
+pub mod ascii;
+pub mod asset;
+pub mod bake;
+pub mod bitmap;
+pub mod fill;
+pub mod format;
+pub mod generate;
+pub mod shapes;
+pub mod svg;
+pub mod theme;
+pub mod tiled;
+
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    color::{Color, Srgba},
+    math::Vec2,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub use format::{parse_level_data, LevelData};
+use shapes::{
+    level_polygon_problems, polygons_solid_at, solid_inside, tile_crossing_problems,
+    validate_level_polygons, validate_polygon_outline, validate_polygons_clear_of_tiles,
+};
+
+#[derive(Clone)]
+pub struct Polygon {
+    pub points: Vec<Vec2>,
+    pub collision_side: f32,
+    pub color: Color,
+}
+
+/// Level path used when none is given on the command line
+pub const DEFAULT_LEVEL_PATH: &str = "assets/level.json";
+
+/// Level baked into the binary, used when the level file can't be read (e.g. on WASM)
+pub const LEVEL_DATA: &[u8] = include_bytes!("../../assets/level.json");
+
+/// Highest tile ID understood by the polygon extraction
+pub const MAX_TILE_ID: u32 = 9;
+
+/// Errors that can occur while loading a level
+#[derive(Debug)]
+pub enum LevelError {
+    /// The level file couldn't be read
+    Io(std::io::Error),
+    /// The level data is not valid UTF-8
+    InvalidUtf8(std::str::Utf8Error),
+    /// The level data is not a valid JSON level
+    InvalidJson(serde_json::Error),
+    /// The level object has a `version` this build doesn't understand
+    UnsupportedVersion(u64),
+    /// The grid size is zero, negative or NaN
+    InvalidGridSize(f32),
+    /// A palette entry is not a valid hex colour
+    InvalidColor(String),
+    /// The tile grid has no rows or no columns
+    EmptyGrid,
+    /// A row has a different length to the first row
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A tile ID outside of `0..=MAX_TILE_ID`
+    UnknownTile {
+        row: usize,
+        column: usize,
+        tile: u32,
+    },
+    /// The edges of a polygon don't form a closed loop
+    UnclosedPolygon { start: Vec2, end: Vec2 },
+    /// A Tiled map uses a feature the importer doesn't support
+    UnsupportedTiledMap(String),
+    /// A free-form polygon has fewer than 3 points
+    TooFewPoints { polygon: usize, points: usize },
+    /// A free-form polygon has two consecutive points in the same place
+    ZeroLengthEdge { polygon: usize, point: Vec2 },
+    /// A free-form polygon's edges cross each other
+    SelfIntersectingPolygon { polygon: usize, point: Vec2 },
+    /// Two free-form polygons cross or touch
+    IntersectingPolygons {
+        first: usize,
+        second: usize,
+        point: Vec2,
+    },
+    /// A free-form polygon crosses or touches a wall built from the tiles
+    PolygonCrossesTiles { polygon: usize, point: Vec2 },
+    /// An ASCII-art level uses a character with no meaning
+    UnknownCharacter {
+        row: usize,
+        column: usize,
+        character: char,
+    },
+    /// An SVG level isn't valid XML or uses path data or transforms the importer can't read
+    InvalidSvg(String),
+    /// A bitmap level isn't a PNG this build can decode
+    InvalidPng(String),
+    /// A baked level blob is truncated or from an incompatible version
+    InvalidBake(String),
+    /// A bitmap level has a pixel colour with no meaning
+    UnknownPixelColor {
+        row: usize,
+        column: usize,
+        color: Srgba,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "failed to read level file: {err}"),
+            LevelError::InvalidUtf8(err) => write!(f, "level data is not valid UTF-8: {err}"),
+            LevelError::InvalidJson(err) => write!(f, "failed to parse level JSON: {err}"),
+            LevelError::UnsupportedVersion(version) => {
+                write!(f, "unsupported level format version {version}")
+            }
+            LevelError::InvalidGridSize(grid_size) => {
+                write!(f, "grid size must be positive, got {grid_size}")
+            }
+            LevelError::InvalidColor(color) => write!(f, "invalid palette colour \"{color}\""),
+            LevelError::EmptyGrid => write!(f, "level grid is empty"),
+            LevelError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {row} has {found} tiles, expected {expected} to match the first row"
+            ),
+            LevelError::UnknownTile { row, column, tile } => {
+                write!(f, "unknown tile ID {tile} at row {row}, column {column}")
+            }
+            LevelError::UnclosedPolygon { start, end } => write!(
+                f,
+                "polygon starting at ({}, {}) does not close, stopped at ({}, {})",
+                start.x, start.y, end.x, end.y
+            ),
+            LevelError::UnsupportedTiledMap(reason) => {
+                write!(f, "unsupported Tiled map: {reason}")
+            }
+            LevelError::TooFewPoints { polygon, points } => {
+                write!(
+                    f,
+                    "polygon {polygon} has {points} points, expected at least 3"
+                )
+            }
+            LevelError::ZeroLengthEdge { polygon, point } => write!(
+                f,
+                "polygon {polygon} has a zero length edge at ({}, {})",
+                point.x, point.y
+            ),
+            LevelError::SelfIntersectingPolygon { polygon, point } => write!(
+                f,
+                "polygon {polygon} intersects itself at ({}, {})",
+                point.x, point.y
+            ),
+            LevelError::IntersectingPolygons {
+                first,
+                second,
+                point,
+            } => write!(
+                f,
+                "polygons {first} and {second} intersect at ({}, {})",
+                point.x, point.y
+            ),
+            LevelError::PolygonCrossesTiles { polygon, point } => write!(
+                f,
+                "polygon {polygon} intersects the tile walls at ({}, {})",
+                point.x, point.y
+            ),
+            LevelError::UnknownCharacter {
+                row,
+                column,
+                character,
+            } => write!(
+                f,
+                "unknown character '{character}' at row {row}, column {column}"
+            ),
+            LevelError::InvalidSvg(message) => write!(f, "invalid SVG level: {message}"),
+            LevelError::InvalidPng(message) => write!(f, "invalid PNG level: {message}"),
+            LevelError::InvalidBake(message) => write!(f, "invalid baked level: {message}"),
+            LevelError::UnknownPixelColor { row, column, color } => write!(
+                f,
+                "unknown pixel colour {} at row {row}, column {column}",
+                color.to_hex()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LevelError::Io(err) => Some(err),
+            LevelError::InvalidUtf8(err) => Some(err),
+            LevelError::InvalidJson(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Check that the tile grid is non-empty, rectangular and only uses known tile IDs
+pub fn validate_tile_grid(tiles: &[Vec<u32>]) -> Result<(), LevelError> {
+    match tile_grid_problems(tiles).into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Every ragged row and unknown tile ID in the tile grid, or just `EmptyGrid`
+pub fn tile_grid_problems(tiles: &[Vec<u32>]) -> Vec<LevelError> {
+    let width = tiles.first().map_or(0, Vec::len);
+    if width == 0 {
+        return vec![LevelError::EmptyGrid];
+    }
+
+    let mut problems = Vec::new();
+    for (row, row_tiles) in tiles.iter().enumerate() {
+        if row_tiles.len() != width {
+            problems.push(LevelError::RaggedRow {
+                row,
+                expected: width,
+                found: row_tiles.len(),
+            });
+        }
+        for (column, &tile) in row_tiles.iter().enumerate() {
+            if tile > MAX_TILE_ID {
+                problems.push(LevelError::UnknownTile { row, column, tile });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Check the tile grid and free-form polygons of a level read without checks
+fn check_level(level: LevelData) -> Result<LevelData, LevelError> {
+    validate_tile_grid(&level.tiles)?;
+    validate_level_polygons(&level.polygons)?;
+    Ok(level)
+}
+
+/// Extract the level's polygons, collecting every problem rather than stopping at the first
+///
+/// Reports the tile grid and free-form polygon problems parsing stops at, tile edges that
+/// don't join into closed polygons, free-form polygons crossing the tile walls, and extracted
+/// polygons with zero length edges or too few points.
+pub fn validate_level(level: &LevelData) -> Result<Vec<Polygon>, Vec<LevelError>> {
+    let mut problems = tile_grid_problems(&level.tiles);
+    let grid_ok = problems.is_empty();
+    problems.extend(level_polygon_problems(&level.polygons));
+
+    // The tile walls are extracted on their own, so they're checked even if the free-form
+    // polygons aren't valid
+    if grid_ok {
+        let tile_level = LevelData {
+            polygons: Vec::new(),
+            ..level.clone()
+        };
+        match generate_level_polygons(&tile_level) {
+            Ok((tile_polygons, _, _)) => {
+                let tile_points: Vec<Vec<Vec2>> = tile_polygons
+                    .into_iter()
+                    .map(|polygon| polygon.points)
+                    .collect();
+                for (i, points) in tile_points.iter().enumerate() {
+                    if let Err(err) = validate_polygon_outline(i, points) {
+                        problems.push(err);
+                    }
+                }
+                problems.extend(tile_crossing_problems(&level.polygons, &tile_points));
+            }
+            Err(err) => problems.push(err),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    generate_level_polygons(level)
+        .map(|(polygons, _, _)| polygons)
+        .map_err(|err| vec![err])
+}
+
+impl LevelData {
+    /// Number of tile columns and rows
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(
+            self.tiles.first().map_or(0, Vec::len) as f32,
+            self.tiles.len() as f32,
+        )
+    }
+
+    /// World position of the top left corner of the tile grid
+    fn origin(&self) -> Vec2 {
+        let size = self.size();
+        Vec2::new(
+            size.x * -self.grid_size / 2.0,
+            size.y * self.grid_size / 2.0,
+        )
+    }
+
+    /// Column and row of the tile containing a world position
+    pub fn world_to_tile(&self, position: Vec2) -> Option<(usize, usize)> {
+        let local = (position - self.origin()) / self.grid_size;
+        let (column, row) = (local.x.floor(), (-local.y).floor());
+        let size = self.size();
+
+        if column < 0.0 || row < 0.0 || column >= size.x || row >= size.y {
+            return None;
+        }
+
+        Some((column as usize, row as usize))
+    }
+
+    /// World position of a point within a tile
+    ///
+    /// `local` goes from (0, 0) at the top left of the tile to (1, 1) at the bottom right.
+    pub fn tile_to_world(&self, column: usize, row: usize, local: Vec2) -> Vec2 {
+        self.origin()
+            + Vec2::new(
+                (column as f32 + local.x) * self.grid_size,
+                -(row as f32 + local.y) * self.grid_size,
+            )
+    }
+
+    /// World position of the centre of a tile
+    pub fn tile_center(&self, column: usize, row: usize) -> Vec2 {
+        self.tile_to_world(column, row, Vec2::splat(0.5))
+    }
+
+    /// Whether the inside of each free-form polygon is solid, read from the collision sides of
+    /// the level's extracted `polygons`
+    ///
+    /// Extraction puts the free-form polygons after the tile walls. Work this out once per load
+    /// and pass it to `is_solid_at` and `nearest_open_position`.
+    pub fn polygons_solid_inside(&self, polygons: &[Polygon]) -> Vec<bool> {
+        polygons[polygons.len().saturating_sub(self.polygons.len())..]
+            .iter()
+            .map(|polygon| polygon.collision_side * calculate_winding_order(&polygon.points) > 0.0)
+            .collect()
+    }
+
+    /// Whether a world position is inside a solid part of a tile, inside a solid free-form
+    /// polygon, or outside the grid, given `polygons_solid_inside`
+    pub fn is_solid_at(&self, position: Vec2, polygons_solid_inside: &[bool]) -> bool {
+        let Some((column, row)) = self.world_to_tile(position) else {
+            return true;
+        };
+
+        // Position within the tile, from (0, 0) at the top left to (1, 1) at the bottom right
+        let tile_origin = self.tile_to_world(column, row, Vec2::ZERO);
+        let local = (position - tile_origin) / self.grid_size * Vec2::new(1.0, -1.0);
+
+        tile_contains_point(self.tiles[row][column], local)
+            || polygons_solid_at(&self.polygons, polygons_solid_inside, position)
+    }
+
+    /// Centre of the open tile closest to a world position, skipping tiles under a solid
+    /// free-form polygon, given `polygons_solid_inside`
+    pub fn nearest_open_position(
+        &self,
+        position: Vec2,
+        polygons_solid_inside: &[bool],
+    ) -> Option<Vec2> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .flat_map(|(row, row_tiles)| {
+                row_tiles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &tile)| tile == 0)
+                    .map(move |(column, _)| (column, row))
+            })
+            .map(|(column, row)| self.tile_center(column, row))
+            .filter(|&center| !polygons_solid_at(&self.polygons, polygons_solid_inside, center))
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+    }
+}
+
+/// Build the level's wall polygons, with colours from its theme picked by its colour seed
+pub fn generate_level_polygons(
+    level: &LevelData,
+) -> Result<(Vec<Polygon>, Vec2, Vec2), LevelError> {
+    generate_level_polygons_with_rng(level, &mut StdRng::seed_from_u64(level.color_seed))
+}
+
+/// Same as `generate_level_polygons`, drawing polygon colours from `rng`
+pub fn generate_level_polygons_with_rng(
+    level: &LevelData,
+    rng: &mut impl Rng,
+) -> Result<(Vec<Polygon>, Vec2, Vec2), LevelError> {
+    validate_tile_grid(&level.tiles)?;
+    validate_level_polygons(&level.polygons)?;
+
+    let json_data = &level.tiles;
+    let grid_size = level.grid_size;
+
+    let size = Vec2::new(json_data[0].len() as f32, json_data.len() as f32);
+
+    let offset = Vec2::new(size.x * -grid_size / 2.0, size.y * grid_size / 2.0);
+
+    let mut line_points: Vec<Vec2> = Vec::new();
+
+    for y in 0..size.y as usize {
+        for x in 0..size.x as usize {
+            let tile = json_data[y][x];
+
+            match tile {
+                1 => {
+                    // Squares
+
+                    // Left edge
+                    if x == 0 || !tile_covers_side(json_data[y][x - 1], TileSide::Right) {
+                        line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                        line_points
+                            .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+                    }
+                    // Right edge
+                    if x == json_data[y].len() - 1
+                        || !tile_covers_side(json_data[y][x + 1], TileSide::Left)
+                    {
+                        line_points
+                            .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+                        line_points.push(Vec2::new(
+                            (x + 1) as f32 * grid_size,
+                            (y + 1) as f32 * grid_size,
+                        ));
+                    }
+                    // Top edge
+                    if y == 0 || !tile_covers_side(json_data[y - 1][x], TileSide::Bottom) {
+                        line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                        line_points
+                            .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+                    }
+                    // Bottom edge
+                    if y == size.y as usize - 1
+                        || !tile_covers_side(json_data[y + 1][x], TileSide::Top)
+                    {
+                        line_points
+                            .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+                        line_points.push(Vec2::new(
+                            (x + 1) as f32 * grid_size,
+                            (y + 1) as f32 * grid_size,
+                        ));
+                    }
+                }
+                2..=5 => {
+                    // Right triangles
+
+                    let triangle_type = tile - 2;
+
+                    match triangle_type {
+                        0 => {
+                            // Bottom left
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_size,
+                                (y + 1) as f32 * grid_size,
+                            ));
+
+                            // Bottom edge
+                            if y == size.y as usize - 1
+                                || !tile_covers_side(json_data[y + 1][x], TileSide::Top)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+
+                            // Left edge
+                            if x == 0 || !tile_covers_side(json_data[y][x - 1], TileSide::Right) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        1 => {
+                            // Bottom right
+
+                            // Hypotenuse
+                            line_points
+                                .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+                            line_points
+                                .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+
+                            // Bottom edge
+                            if y == size.y as usize - 1
+                                || !tile_covers_side(json_data[y + 1][x], TileSide::Top)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+
+                            // Right edge
+                            if x == json_data[y].len() - 1
+                                || !tile_covers_side(json_data[y][x + 1], TileSide::Left)
+                            {
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        2 => {
+                            // Top left
+
+                            // Hypotenuse
+                            line_points
+                                .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+                            line_points
+                                .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+
+                            // Top edge
+                            if y == 0 || !tile_covers_side(json_data[y - 1][x], TileSide::Bottom) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                            }
+
+                            // Left edge
+                            if x == 0 || !tile_covers_side(json_data[y][x - 1], TileSide::Right) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        3 => {
+                            // Top right
+
+                            // Hypotenuse
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_size,
+                                (y + 1) as f32 * grid_size,
+                            ));
+                            line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+
+                            // Top edge
+                            if y == 0 || !tile_covers_side(json_data[y - 1][x], TileSide::Bottom) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                            }
+
+                            // Right edge
+                            if x == json_data[y].len() - 1
+                                || !tile_covers_side(json_data[y][x + 1], TileSide::Left)
+                            {
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                6..=9 => {
+                    // Isosceles triangles
+
+                    let triangle_type = tile - 6;
+
+                    let center =
+                        Vec2::new((x as f32 + 0.5) * grid_size, (y as f32 + 0.5) * grid_size);
+
+                    match triangle_type {
+                        0 => {
+                            // Bottom
+
+                            // Left side
+                            line_points
+                                .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Right side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_size,
+                                (y + 1) as f32 * grid_size,
+                            ));
+                            line_points.push(center);
+
+                            // Bottom edge
+                            if y == size.y as usize - 1
+                                || !tile_covers_side(json_data[y + 1][x], TileSide::Top)
+                            {
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        1 => {
+                            // Top
+
+                            // Left side
+                            line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Right side
+                            line_points
+                                .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Top edge
+                            if y == 0 || !tile_covers_side(json_data[y - 1][x], TileSide::Bottom) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        2 => {
+                            // Left
+
+                            // Top side
+                            line_points.push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Bottom side
+                            line_points
+                                .push(Vec2::new(x as f32 * grid_size, (y + 1) as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Left edge
+                            if x == 0 || !tile_covers_side(json_data[y][x - 1], TileSide::Right) {
+                                line_points
+                                    .push(Vec2::new(x as f32 * grid_size, y as f32 * grid_size));
+                                line_points.push(Vec2::new(
+                                    x as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        3 => {
+                            // Right
+
+                            // Top side
+                            line_points
+                                .push(Vec2::new((x + 1) as f32 * grid_size, y as f32 * grid_size));
+                            line_points.push(center);
+
+                            // Bottom side
+                            line_points.push(Vec2::new(
+                                (x + 1) as f32 * grid_size,
+                                (y + 1) as f32 * grid_size,
+                            ));
+                            line_points.push(center);
+
+                            // Right edge
+                            if x == json_data[y].len() - 1
+                                || !tile_covers_side(json_data[y][x + 1], TileSide::Left)
+                            {
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    y as f32 * grid_size,
+                                ));
+                                line_points.push(Vec2::new(
+                                    (x + 1) as f32 * grid_size,
+                                    (y + 1) as f32 * grid_size,
+                                ));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Pair up the points into lines and join collinear runs into single lines
+    let mut lines: Vec<[Vec2; 2]> = line_points
+        .chunks_exact(2)
+        .map(|line| [line[0], line[1]])
+        .collect();
+    merge_collinear_lines(&mut lines);
+
+    for point in lines.iter_mut().flatten() {
+        point.x += offset.x;
+        point.y *= -1.0;
+        point.y += offset.y;
+    }
+
+    // Separate the lines into polygons
+    let mut polygons: Vec<Polygon> = Vec::new();
+
+    // Pick polygon colours from the theme's palette if it has one
+    let palette = level.theme().palette;
+    let mut random_color = || {
+        if palette.is_empty() {
+            Color::srgb(
+                rng.random_range(0.0..=1.0),
+                rng.random_range(0.0..=1.0),
+                rng.random_range(0.0..=1.0),
+            )
+        } else {
+            palette[rng.random_range(0..palette.len())]
+        }
+    };
+
+    let container_color = palette.first().copied().unwrap_or_else(&mut random_color);
+
+    let polygon_points = join_lines_into_polygons(&lines)?;
+    validate_polygons_clear_of_tiles(&level.polygons, &polygon_points)?;
+    let nesting = polygon_nesting(&polygon_points);
+    let free_solid_inside = solid_inside(&level.polygons, &polygon_points);
+
+    // Colour outer polygons before the polygons nested in them, so holes can share their
+    // parent's colour. Outer boundaries with holes in them are the level's container.
+    let mut colors = vec![container_color; polygon_points.len()];
+    let mut by_depth: Vec<usize> = (0..polygon_points.len()).collect();
+    by_depth.sort_by_key(|&i| nesting[i].depth);
+    for i in by_depth {
+        colors[i] = match nesting[i].parent {
+            Some(parent) if nesting[i].depth % 2 == 1 => colors[parent],
+            None if nesting.iter().any(|other| other.parent == Some(i)) => container_color,
+            _ => random_color(),
+        };
+    }
+
+    for ((points, nesting), color) in polygon_points.into_iter().zip(nesting).zip(colors) {
+        // Even depths enclose solid tiles and odd depths enclose open space, so collide
+        // from the other side of holes
+        let mut collision_side = calculate_winding_order(&points).signum();
+        if nesting.depth % 2 == 1 {
+            collision_side *= -1.0;
+        }
+
+        polygons.push(Polygon {
+            points,
+            collision_side,
+            color,
+        });
+    }
+
+    // Free-form polygons are already in world space
+    for (level_polygon, solid_inside) in level.polygons.iter().zip(free_solid_inside) {
+        let mut collision_side = calculate_winding_order(&level_polygon.points).signum();
+        if !solid_inside {
+            collision_side *= -1.0;
+        }
+
+        polygons.push(Polygon {
+            points: level_polygon.points.clone(),
+            collision_side,
+            color: level_polygon.color.unwrap_or_else(&mut random_color),
+        });
+    }
+
+    Ok((polygons, size, size / 2.0))
+}
+
+/// Where a polygon sits in the hierarchy of outer boundaries, holes and islands
+struct PolygonNesting {
+    /// Number of polygons enclosing this one
+    depth: usize,
+    /// Innermost polygon enclosing this one
+    parent: Option<usize>,
+}
+
+/// Work out which polygons enclose which
+///
+/// The extracted polygons never cross, so a polygon is inside another if the midpoint of its
+/// first edge is. Edges are never shared between polygons, so that point can't be on the
+/// other polygon's outline.
+fn polygon_nesting(polygons: &[Vec<Vec2>]) -> Vec<PolygonNesting> {
+    let areas: Vec<f32> = polygons
+        .iter()
+        .map(|points| calculate_winding_order(points).abs())
+        .collect();
+
+    polygons
+        .iter()
+        .enumerate()
+        .map(|(i, points)| {
+            let test_point = (points[0] + points[1]) / 2.0;
+            let enclosing: Vec<usize> = (0..polygons.len())
+                .filter(|&j| j != i && point_in_polygon(&polygons[j], test_point))
+                .collect();
+
+            PolygonNesting {
+                depth: enclosing.len(),
+                parent: enclosing
+                    .into_iter()
+                    .min_by(|&a, &b| areas[a].total_cmp(&areas[b])),
+            }
+        })
+        .collect()
+}
+
+/// Key for looking up lines by their end points
+///
+/// Points are compared exactly, like the `==` checks they replace, with `-0.0` folded into `0.0`.
+fn point_key(point: Vec2) -> (u32, u32) {
+    ((point.x + 0.0).to_bits(), (point.y + 0.0).to_bits())
+}
+
+/// Index of the lines touching each end point
+///
+/// Lines are `None` once they've been merged away, and are left in the index until then.
+fn index_line_ends(lines: &[Option<[Vec2; 2]>]) -> HashMap<(u32, u32), Vec<usize>> {
+    let mut line_ends: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(line) = line {
+            for point in line {
+                line_ends.entry(point_key(*point)).or_default().push(i);
+            }
+        }
+    }
+    line_ends
+}
+
+/// Remove superfluous points by merging parallel lines that share an end point
+///
+/// Merged lines are appended to the end of the list. Each merge only revisits the points of
+/// the new line, so this runs in roughly linear time instead of rescanning every pair.
+fn merge_collinear_lines(lines: &mut Vec<[Vec2; 2]>) {
+    let mut merged: Vec<Option<[Vec2; 2]>> = lines.drain(..).map(Some).collect();
+    let mut line_ends = index_line_ends(&merged);
+
+    let mut pending: Vec<Vec2> = merged.iter().flatten().flatten().copied().collect();
+    pending.reverse();
+
+    while let Some(shared) = pending.pop() {
+        let Some(touching) = line_ends.get_mut(&point_key(shared)) else {
+            continue;
+        };
+        touching.retain(|&i| merged[i].is_some());
+
+        // Find the first pair of parallel lines through this point
+        let mut pair = None;
+        'outer: for (a, &i) in touching.iter().enumerate() {
+            for &j in &touching[a + 1..] {
+                if let (Some(line_1), Some(line_2)) = (merged[i], merged[j]) {
+                    let dot = (line_1[0] - line_1[1])
+                        .normalize()
+                        .dot((line_2[0] - line_2[1]).normalize());
+                    if dot.abs() == 1.0 {
+                        pair = Some((i, j, line_1, line_2));
+                        break 'outer;
+                    }
+                }
+            }
+        }
+        let Some((i, j, line_1, line_2)) = pair else {
+            continue;
+        };
+
+        // Replace the pair with a line between their unique points
+        let unique_point = |line: [Vec2; 2]| if line[0] == shared { line[1] } else { line[0] };
+        merged[i] = None;
+        merged[j] = None;
+        let line = [unique_point(line_1), unique_point(line_2)];
+
+        let new_index = merged.len();
+        merged.push(Some(line));
+        for point in line {
+            line_ends
+                .entry(point_key(point))
+                .or_default()
+                .push(new_index);
+        }
+
+        // The shared point may have another parallel pair, and the new line may merge further
+        pending.extend([shared, line[0], line[1]]);
+    }
+
+    lines.extend(merged.into_iter().flatten());
+}
+
+/// Walk the lines into closed polygons
+///
+/// Each polygon starts from the first unused line and repeatedly follows the first unused line
+/// touching its current end point, until it gets back to where it started.
+fn join_lines_into_polygons(lines: &[[Vec2; 2]]) -> Result<Vec<Vec<Vec2>>, LevelError> {
+    let mut line_ends = index_line_ends(&lines.iter().copied().map(Some).collect::<Vec<_>>());
+    let mut used = vec![false; lines.len()];
+    let mut polygons = Vec::new();
+
+    for first_line in 0..lines.len() {
+        if used[first_line] {
+            continue;
+        }
+        used[first_line] = true;
+
+        let mut polygon_lines = lines[first_line].to_vec();
+        let start_vert = polygon_lines[0];
+        let mut current_vert = polygon_lines[1];
+
+        // While the polygon is not closed
+        while start_vert != current_vert {
+            let next_line = line_ends
+                .get_mut(&point_key(current_vert))
+                .and_then(|touching| {
+                    touching.retain(|&i| !used[i]);
+                    touching.first().copied()
+                });
+            let Some(next_line) = next_line else {
+                return Err(LevelError::UnclosedPolygon {
+                    start: start_vert,
+                    end: current_vert,
+                });
+            };
+            used[next_line] = true;
+
+            // Continue from whichever end of the line we didn't arrive at
+            let [line_start, line_end] = lines[next_line];
+            current_vert = if line_start == current_vert {
+                line_end
+            } else {
+                line_start
+            };
+            polygon_lines.push(current_vert);
+        }
+
+        polygons.push(polygon_lines);
+    }
+
+    Ok(polygons)
+}
+
+#[derive(Clone, Copy)]
+enum TileSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Whether a tile is solid along the whole of the given side
+///
+/// Edges are only generated against neighbours that don't cover the shared side,
+/// so slopes and spikes next to each other still form closed polygons.
+fn tile_covers_side(tile: u32, side: TileSide) -> bool {
+    match (tile, side) {
+        // Square
+        (1, _) => true,
+        // Right triangles
+        (2, TileSide::Bottom | TileSide::Left) => true,
+        (3, TileSide::Bottom | TileSide::Right) => true,
+        (4, TileSide::Top | TileSide::Left) => true,
+        (5, TileSide::Top | TileSide::Right) => true,
+        // Isosceles triangles (only the base is a full side)
+        (6, TileSide::Bottom) => true,
+        (7, TileSide::Top) => true,
+        (8, TileSide::Left) => true,
+        (9, TileSide::Right) => true,
+        _ => false,
+    }
+}
+
+/// Closed outline of a tile's solid part, in the same local coordinates as `tile_contains_point`
+pub fn tile_shape(tile: u32) -> &'static [Vec2] {
+    const TOP_LEFT: Vec2 = Vec2::new(0.0, 0.0);
+    const TOP_RIGHT: Vec2 = Vec2::new(1.0, 0.0);
+    const BOTTOM_LEFT: Vec2 = Vec2::new(0.0, 1.0);
+    const BOTTOM_RIGHT: Vec2 = Vec2::new(1.0, 1.0);
+    const CENTER: Vec2 = Vec2::new(0.5, 0.5);
+
+    match tile {
+        // Square
+        1 => &[TOP_LEFT, TOP_RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT, TOP_LEFT],
+        // Right triangles
+        2 => &[TOP_LEFT, BOTTOM_RIGHT, BOTTOM_LEFT, TOP_LEFT],
+        3 => &[TOP_RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT, TOP_RIGHT],
+        4 => &[TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT, TOP_LEFT],
+        5 => &[TOP_LEFT, TOP_RIGHT, BOTTOM_RIGHT, TOP_LEFT],
+        // Isosceles triangles
+        6 => &[BOTTOM_LEFT, CENTER, BOTTOM_RIGHT, BOTTOM_LEFT],
+        7 => &[TOP_LEFT, TOP_RIGHT, CENTER, TOP_LEFT],
+        8 => &[TOP_LEFT, CENTER, BOTTOM_LEFT, TOP_LEFT],
+        9 => &[TOP_RIGHT, BOTTOM_RIGHT, CENTER, TOP_RIGHT],
+        _ => &[],
+    }
+}
+
+/// Whether a point inside a tile is in its solid part
+///
+/// `local` goes from (0, 0) at the top left of the tile to (1, 1) at the bottom right.
+fn tile_contains_point(tile: u32, local: Vec2) -> bool {
+    let Vec2 { x, y } = local;
+    match tile {
+        // Square
+        1 => true,
+        // Right triangles
+        2 => y >= x,
+        3 => x + y >= 1.0,
+        4 => x + y <= 1.0,
+        5 => y <= x,
+        // Isosceles triangles
+        6 => (x - 0.5).abs() <= y - 0.5,
+        7 => (x - 0.5).abs() <= 0.5 - y,
+        8 => (y - 0.5).abs() <= 0.5 - x,
+        9 => (y - 0.5).abs() <= x - 0.5,
+        _ => false,
+    }
+}
+
+fn calculate_winding_order(vertices: &[Vec2]) -> f32 {
+    let mut sum = 0.0;
+
+    for i in 0..vertices.len() {
+        let p1 = vertices[i];
+        let p2 = vertices[(i + 1) % vertices.len()];
+        sum += (p2.x - p1.x) * (p2.y + p1.y);
+    }
+
+    sum
+}
+
+/// Even-odd test, casting a ray along +X from the point
+///
+/// Edges are treated as half open in Y so rays through a vertex are only counted once.
+fn point_in_polygon(polygon_lines: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+
+    for i in 1..polygon_lines.len() {
+        let start = polygon_lines[i - 1];
+        let end = polygon_lines[i];
+
+        if (start.y > point.y) != (end.y > point.y) {
+            let crossing_x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+            if crossing_x > point.x {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
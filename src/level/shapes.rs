@@ -0,0 +1,208 @@
+use bevy::{color::Color, math::Vec2};
+use serde::{Deserialize, Serialize};
+
+use super::{calculate_winding_order, point_in_polygon, polygon_nesting, LevelError};
+use crate::utils::line_intersect;
+
+/// Which side of a free-form polygon is solid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionSide {
+    /// Keep things inside, like a cave wall
+    Inside,
+    /// Keep things outside, like a rock
+    Outside,
+}
+
+/// A wall given directly as vertices rather than built from tiles
+///
+/// Points are in world space and closed, the first point is repeated at the end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelPolygon {
+    pub points: Vec<Vec2>,
+    /// `None` to be solid inside when the space around it is open, so outermost polygons are
+    /// `Inside` and nested ones alternate
+    pub collision_side: Option<CollisionSide>,
+    /// `None` for a colour from the level palette, or a random one
+    pub color: Option<Color>,
+}
+
+/// Check that every polygon is a simple closed loop
+pub fn validate_level_polygons(polygons: &[LevelPolygon]) -> Result<(), LevelError> {
+    match level_polygon_problems(polygons).into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Every reason the polygons aren't simple closed loops, at most one per polygon and one per
+/// pair of polygons
+pub fn level_polygon_problems(polygons: &[LevelPolygon]) -> Vec<LevelError> {
+    let mut problems = Vec::new();
+
+    for (polygon, level_polygon) in polygons.iter().enumerate() {
+        let points = &level_polygon.points;
+        if let Err(err) = validate_polygon_outline(polygon, points) {
+            problems.push(err);
+            continue;
+        }
+
+        // Every pair of edges that aren't next to each other
+        let edge_count = points.len() - 1;
+        let crossing = (0..edge_count).find_map(|i| {
+            (i + 2..edge_count)
+                .filter(|&j| !(i == 0 && j == edge_count - 1))
+                .find_map(|j| line_intersect(points[i], points[i + 1], points[j], points[j + 1]))
+        });
+        if let Some(point) = crossing {
+            problems.push(LevelError::SelfIntersectingPolygon { polygon, point });
+        }
+    }
+
+    // Nesting is worked out assuming polygons never cross or touch
+    for (first, first_polygon) in polygons.iter().enumerate() {
+        for (second, second_polygon) in polygons.iter().enumerate().skip(first + 1) {
+            if let Some(point) = polygons_crossing(&first_polygon.points, &second_polygon.points) {
+                problems.push(LevelError::IntersectingPolygons {
+                    first,
+                    second,
+                    point,
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Check that no free-form polygon crosses or touches the walls built from the tiles
+pub fn validate_polygons_clear_of_tiles(
+    polygons: &[LevelPolygon],
+    tile_polygons: &[Vec<Vec2>],
+) -> Result<(), LevelError> {
+    match tile_crossing_problems(polygons, tile_polygons)
+        .into_iter()
+        .next()
+    {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Every free-form polygon that crosses or touches the walls built from the tiles
+pub fn tile_crossing_problems(
+    polygons: &[LevelPolygon],
+    tile_polygons: &[Vec<Vec2>],
+) -> Vec<LevelError> {
+    polygons
+        .iter()
+        .enumerate()
+        .filter_map(|(polygon, level_polygon)| {
+            tile_polygons.iter().find_map(|tile_polygon| {
+                polygons_crossing(&level_polygon.points, tile_polygon)
+                    .map(|point| LevelError::PolygonCrossesTiles { polygon, point })
+            })
+        })
+        .collect()
+}
+
+/// A point where an edge of one closed polygon meets an edge of another
+fn polygons_crossing(first: &[Vec2], second: &[Vec2]) -> Option<Vec2> {
+    first.windows(2).find_map(|a| {
+        second
+            .windows(2)
+            .find_map(|b| line_intersect(a[0], a[1], b[0], b[1]))
+    })
+}
+
+/// Check that a polygon's points are closed, with at least 3 distinct corners in a row
+pub fn validate_polygon_outline(polygon: usize, points: &[Vec2]) -> Result<(), LevelError> {
+    if let (Some(&start), Some(&end)) = (points.first(), points.last()) {
+        if start != end {
+            return Err(LevelError::UnclosedPolygon { start, end });
+        }
+    }
+
+    // Closed loops repeat the first point, so a triangle has four
+    if points.len() < 4 {
+        return Err(LevelError::TooFewPoints {
+            polygon,
+            points: points.len().saturating_sub(1),
+        });
+    }
+
+    if let Some(edge) = points.windows(2).find(|edge| edge[0] == edge[1]) {
+        return Err(LevelError::ZeroLengthEdge {
+            polygon,
+            point: edge[0],
+        });
+    }
+
+    Ok(())
+}
+
+/// Whether the inside of each polygon is solid, given the walls built from the tiles
+///
+/// Polygons without a collision side are solid inside when the space around them is open, so
+/// the outermost polygon is a container and the polygons in it are islands. The space around a
+/// polygon is the inside of the innermost tile wall or polygon enclosing it, and is solid when
+/// nothing encloses it.
+pub fn solid_inside(polygons: &[LevelPolygon], tile_polygons: &[Vec<Vec2>]) -> Vec<bool> {
+    // Tile walls at even depths enclose solid tiles, counting only the other tile walls
+    let tile_solid_inside: Vec<bool> = polygon_nesting(tile_polygons)
+        .iter()
+        .map(|nesting| nesting.depth % 2 == 0)
+        .collect();
+
+    let points: Vec<Vec<Vec2>> = tile_polygons
+        .iter()
+        .cloned()
+        .chain(polygons.iter().map(|polygon| polygon.points.clone()))
+        .collect();
+    let nesting = &polygon_nesting(&points)[tile_polygons.len()..];
+
+    // Work outwards in, so the polygon around each one is done first
+    let mut solid = vec![false; polygons.len()];
+    let mut by_depth: Vec<usize> = (0..polygons.len()).collect();
+    by_depth.sort_by_key(|&i| nesting[i].depth);
+    for i in by_depth {
+        let solid_around = match nesting[i].parent {
+            None => true,
+            Some(parent) if parent < tile_polygons.len() => tile_solid_inside[parent],
+            Some(parent) => solid[parent - tile_polygons.len()],
+        };
+        solid[i] = match polygons[i].collision_side {
+            Some(CollisionSide::Inside) => false,
+            Some(CollisionSide::Outside) => true,
+            None => !solid_around,
+        };
+    }
+
+    solid
+}
+
+/// Whether a world position is inside a solid polygon, given `solid_inside` for the polygons
+///
+/// Only the innermost polygon around the position counts, so open space inside an island's
+/// hole isn't solid.
+pub fn polygons_solid_at(polygons: &[LevelPolygon], solid_inside: &[bool], position: Vec2) -> bool {
+    let area = |polygon: &LevelPolygon| calculate_winding_order(&polygon.points).abs();
+
+    polygons
+        .iter()
+        .zip(solid_inside.iter().copied())
+        .filter(|(polygon, _)| point_in_polygon(&polygon.points, position))
+        .min_by(|(a, _), (b, _)| area(a).total_cmp(&area(b)))
+        .is_some_and(|(_, solid)| solid)
+}
+
+/// Number of columns and rows of a grid centred on the origin that covers every polygon
+pub fn covering_grid_size(polygons: &[LevelPolygon], grid_size: f32) -> (usize, usize) {
+    let extent = polygons
+        .iter()
+        .flat_map(|polygon| &polygon.points)
+        .fold(Vec2::ZERO, |extent, point| extent.max(point.abs()));
+
+    let cells = (extent * 2.0 / grid_size).ceil().max(Vec2::ONE);
+    (cells.x as usize, cells.y as usize)
+}
@@ -0,0 +1,520 @@
+use bevy::{
+    color::{Color, Srgba},
+    math::{Affine2, Vec2},
+};
+
+use super::{
+    calculate_winding_order, check_level,
+    format::DEFAULT_GRID_SIZE,
+    shapes::{covering_grid_size, CollisionSide, LevelPolygon},
+    LevelData, LevelError, Polygon,
+};
+
+/// Extension of SVG levels
+pub const SVG_EXTENSION: &str = "svg";
+
+// Exported drawing parameters
+const STROKE_WIDTH: f32 = 2.0;
+/// Length of the ticks showing which side of each edge collides
+const MARKER_LENGTH: f32 = 6.0;
+/// Class of the group holding the collision side ticks, skipped when importing
+const MARKER_CLASS: &str = "collision-sides";
+/// Attribute recording each polygon's collision side, so exported levels import unchanged
+const COLLISION_SIDE_ATTRIBUTE: &str = "data-collision-side";
+
+/// Number of line segments each curve is flattened into when importing
+const CURVE_SEGMENTS: usize = 8;
+
+/// Draw level polygons as an SVG, centred on the origin with Y flipped to point down
+///
+/// Each polygon is outlined in its colour, with a short tick at the middle of every edge
+/// pointing towards the side things collide from.
+pub fn polygons_to_svg(polygons: &[Polygon], size: Vec2) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n",
+        size.x,
+        size.y,
+        -size.x / 2.0,
+        -size.y / 2.0,
+        size.x,
+        size.y
+    );
+
+    for polygon in polygons {
+        let color = polygon.color.to_srgba().to_hex();
+        let open_points = &polygon.points[..polygon.points.len().saturating_sub(1)];
+        let points: Vec<String> = open_points
+            .iter()
+            .map(|point| format!("{},{}", point.x, -point.y))
+            .collect();
+
+        // Solid on the inside if the collision side is to the outside of the winding
+        let solid_inside = polygon.collision_side * calculate_winding_order(&polygon.points) > 0.0;
+        let collision_side = if solid_inside { "outside" } else { "inside" };
+
+        svg += &format!(
+            "  <polygon points=\"{}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"{STROKE_WIDTH}\" {COLLISION_SIDE_ATTRIBUTE}=\"{collision_side}\"/>\n",
+            points.join(" ")
+        );
+    }
+
+    svg += &format!("  <g class=\"{MARKER_CLASS}\" stroke-width=\"1\">\n");
+    for polygon in polygons {
+        let color = polygon.color.to_srgba().to_hex();
+        let ticks: Vec<String> = polygon
+            .points
+            .windows(2)
+            .map(|edge| {
+                // `side_of_line_detection` is positive to the left of an edge
+                let left = (edge[1] - edge[0]).perp().normalize_or_zero();
+                let start = (edge[0] + edge[1]) / 2.0;
+                let end = start + left * polygon.collision_side * MARKER_LENGTH;
+                format!("M{},{} L{},{}", start.x, -start.y, end.x, -end.y)
+            })
+            .collect();
+
+        svg += &format!("    <path d=\"{}\" stroke=\"{color}\"/>\n", ticks.join(" "));
+    }
+    svg += "  </g>\n</svg>\n";
+
+    svg
+}
+
+/// Parse the `<polygon>` and `<path>` elements of an SVG into a free-form polygon level
+///
+/// * Coordinates are relative to the centre of the `viewBox` (or the width and height),
+///   with Y flipped to point up, and `transform`s on elements and groups are applied
+/// * Every subpath is a closed polygon, curves are flattened and arcs become straight lines.
+///   Subpaths with fewer than 3 points are skipped.
+/// * Colours come from the stroke, or the fill if there's no stroke
+/// * `data-collision-side` (`inside` or `outside`) sets the collision side, as written by
+///   `polygons_to_svg`
+///
+/// Levels use the default grid size and spawns, with an empty tile grid covering the polygons.
+pub fn parse_svg_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    read_svg_level(level_data).and_then(check_level)
+}
+
+/// Same as `parse_svg_level`, without checking the polygons
+pub fn read_svg_level(level_data: &[u8]) -> Result<LevelData, LevelError> {
+    let text = std::str::from_utf8(level_data).map_err(LevelError::InvalidUtf8)?;
+    let document =
+        roxmltree::Document::parse(text).map_err(|err| LevelError::InvalidSvg(err.to_string()))?;
+    let root = document.root_element();
+
+    // Centre of the drawing, which becomes the world origin
+    let view_box = root
+        .attribute("viewBox")
+        .map(parse_numbers)
+        .transpose()?
+        .filter(|view_box| view_box.len() == 4);
+    let center = match view_box {
+        Some(view_box) => Vec2::new(
+            view_box[0] + view_box[2] / 2.0,
+            view_box[1] + view_box[3] / 2.0,
+        ),
+        None => {
+            let length = |name| {
+                root.attribute(name)
+                    .and_then(|value: &str| value.trim_end_matches("px").parse::<f32>().ok())
+                    .unwrap_or(0.0)
+            };
+            Vec2::new(length("width"), length("height")) / 2.0
+        }
+    };
+    let to_world = Affine2::from_scale(Vec2::new(1.0, -1.0)) * Affine2::from_translation(-center);
+
+    let mut polygons = Vec::new();
+    collect_polygons(root, to_world, &mut polygons)?;
+
+    let (columns, rows) = covering_grid_size(&polygons, DEFAULT_GRID_SIZE);
+    let mut level = LevelData::from_tiles(vec![vec![0; columns]; rows]);
+    level.polygons = polygons;
+
+    Ok(level)
+}
+
+fn collect_polygons(
+    node: roxmltree::Node,
+    parent_transform: Affine2,
+    polygons: &mut Vec<LevelPolygon>,
+) -> Result<(), LevelError> {
+    let is_marker_group = node
+        .attribute("class")
+        .is_some_and(|class| class.split_whitespace().any(|class| class == MARKER_CLASS));
+    if is_marker_group || node.tag_name().name() == "defs" {
+        return Ok(());
+    }
+
+    let transform = match node.attribute("transform") {
+        Some(transform) => parent_transform * parse_transform(transform)?,
+        None => parent_transform,
+    };
+
+    let outlines = match node.tag_name().name() {
+        "polygon" => {
+            let numbers = parse_numbers(node.attribute("points").unwrap_or_default())?;
+            vec![numbers
+                .chunks_exact(2)
+                .map(|pair| Vec2::new(pair[0], pair[1]))
+                .collect()]
+        }
+        "path" => parse_path(node.attribute("d").unwrap_or_default())?,
+        _ => Vec::new(),
+    };
+
+    let color = style_color(node);
+    let collision_side = match node.attribute(COLLISION_SIDE_ATTRIBUTE) {
+        Some("inside") => Some(CollisionSide::Inside),
+        Some("outside") => Some(CollisionSide::Outside),
+        _ => None,
+    };
+
+    for outline in outlines {
+        let mut points: Vec<Vec2> = Vec::new();
+        for point in outline {
+            let point = transform.transform_point2(point);
+            if points.last() != Some(&point) {
+                points.push(point);
+            }
+        }
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 3 {
+            continue;
+        }
+        points.push(points[0]);
+
+        polygons.push(LevelPolygon {
+            points,
+            collision_side,
+            color,
+        });
+    }
+
+    for child in node.children().filter(roxmltree::Node::is_element) {
+        collect_polygons(child, transform, polygons)?;
+    }
+
+    Ok(())
+}
+
+/// Stroke colour, or fill colour if there's no stroke, from attributes or the `style`
+fn style_color(node: roxmltree::Node) -> Option<Color> {
+    let style_property = |name: &str| {
+        node.attribute("style").and_then(|style| {
+            style.split(';').find_map(|declaration| {
+                let (property, value) = declaration.split_once(':')?;
+                (property.trim() == name).then(|| value.trim())
+            })
+        })
+    };
+
+    ["stroke", "fill"]
+        .into_iter()
+        .filter_map(|name| style_property(name).or_else(|| node.attribute(name)))
+        .find_map(|value| Srgba::hex(value).ok())
+        .map(Color::Srgba)
+}
+
+/// Parse an SVG `transform` list, e.g. `translate(10, 20) rotate(45)`
+fn parse_transform(transform: &str) -> Result<Affine2, LevelError> {
+    let mut result = Affine2::IDENTITY;
+
+    for function in transform.split_inclusive(')') {
+        let Some((name, arguments)) = function.split_once('(') else {
+            continue;
+        };
+        let name = name.trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let arguments = parse_numbers(arguments.trim_end_matches(')'))?;
+        let argument = |i: usize| arguments.get(i).copied();
+
+        let matrix = match (name, arguments.len()) {
+            ("matrix", 6) => Affine2::from_cols_array(&[
+                arguments[0],
+                arguments[1],
+                arguments[2],
+                arguments[3],
+                arguments[4],
+                arguments[5],
+            ]),
+            ("translate", 1 | 2) => {
+                Affine2::from_translation(Vec2::new(arguments[0], argument(1).unwrap_or(0.0)))
+            }
+            ("scale", 1 | 2) => {
+                Affine2::from_scale(Vec2::new(arguments[0], argument(1).unwrap_or(arguments[0])))
+            }
+            ("rotate", 1 | 3) => {
+                let pivot = Vec2::new(argument(1).unwrap_or(0.0), argument(2).unwrap_or(0.0));
+                Affine2::from_translation(pivot)
+                    * Affine2::from_angle(arguments[0].to_radians())
+                    * Affine2::from_translation(-pivot)
+            }
+            ("skewX", 1) => Affine2::from_cols_array(&[
+                1.0,
+                0.0,
+                arguments[0].to_radians().tan(),
+                1.0,
+                0.0,
+                0.0,
+            ]),
+            ("skewY", 1) => Affine2::from_cols_array(&[
+                1.0,
+                arguments[0].to_radians().tan(),
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            ]),
+            _ => {
+                return Err(LevelError::InvalidSvg(format!(
+                    "unsupported transform \"{}\"",
+                    function.trim()
+                )))
+            }
+        };
+        result *= matrix;
+    }
+
+    Ok(result)
+}
+
+/// Parse a list of numbers separated by whitespace and/or commas
+fn parse_numbers(text: &str) -> Result<Vec<f32>, LevelError> {
+    let mut parser = PathParser::new(text);
+    let mut numbers = Vec::new();
+    while let Some(number) = parser.number() {
+        numbers.push(number);
+    }
+
+    if parser.at_end() {
+        Ok(numbers)
+    } else {
+        Err(LevelError::InvalidSvg(format!(
+            "invalid number list \"{text}\""
+        )))
+    }
+}
+
+/// Parse path data into the outline of each subpath
+fn parse_path(data: &str) -> Result<Vec<Vec<Vec2>>, LevelError> {
+    let invalid = || LevelError::InvalidSvg(format!("invalid path data \"{data}\""));
+
+    let mut parser = PathParser::new(data);
+    let mut subpaths: Vec<Vec<Vec2>> = Vec::new();
+    let mut current = Vec2::ZERO;
+    let mut subpath_start = Vec2::ZERO;
+    // Last control point, for reflecting into smooth curves
+    let mut last_control: Option<(u8, Vec2)> = None;
+    let mut closed = false;
+
+    let mut command = parser.command().ok_or_else(invalid)?;
+    loop {
+        // Drawing straight after a close starts a new subpath where the closed one started
+        if closed && !matches!(command.to_ascii_uppercase(), b'M' | b'Z') {
+            subpaths.push(vec![subpath_start]);
+        }
+        closed = command.eq_ignore_ascii_case(&b'Z');
+
+        let relative = command.is_ascii_lowercase();
+        let offset = if relative { current } else { Vec2::ZERO };
+        let point = |parser: &mut PathParser| -> Result<Vec2, LevelError> {
+            let x = parser.number().ok_or_else(invalid)?;
+            let y = parser.number().ok_or_else(invalid)?;
+            Ok(offset + Vec2::new(x, y))
+        };
+
+        let mut control = None;
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                current = point(&mut parser)?;
+                subpath_start = current;
+                subpaths.push(vec![current]);
+                // Further coordinate pairs are implicit line-tos
+                command = if relative { b'l' } else { b'L' };
+            }
+            b'L' => current = point(&mut parser)?,
+            b'H' => current.x = offset.x + parser.number().ok_or_else(invalid)?,
+            b'V' => current.y = offset.y + parser.number().ok_or_else(invalid)?,
+            b'C' | b'S' => {
+                let control_1 = if command.eq_ignore_ascii_case(&b'C') {
+                    point(&mut parser)?
+                } else {
+                    match last_control {
+                        Some((b'C', last)) => current * 2.0 - last,
+                        _ => current,
+                    }
+                };
+                let control_2 = point(&mut parser)?;
+                let end = point(&mut parser)?;
+                let start = current;
+                push_curve(&mut subpaths, |t| {
+                    let u = 1.0 - t;
+                    start * u * u * u
+                        + control_1 * 3.0 * u * u * t
+                        + control_2 * 3.0 * u * t * t
+                        + end * t * t * t
+                });
+                current = end;
+                control = Some((b'C', control_2));
+            }
+            b'Q' | b'T' => {
+                let control_1 = if command.eq_ignore_ascii_case(&b'Q') {
+                    point(&mut parser)?
+                } else {
+                    match last_control {
+                        Some((b'Q', last)) => current * 2.0 - last,
+                        _ => current,
+                    }
+                };
+                let end = point(&mut parser)?;
+                let start = current;
+                push_curve(&mut subpaths, |t| {
+                    let u = 1.0 - t;
+                    start * u * u + control_1 * 2.0 * u * t + end * t * t
+                });
+                current = end;
+                control = Some((b'Q', control_1));
+            }
+            b'A' => {
+                // Radii, rotation and flags are read but the arc is drawn as a straight line
+                for _ in 0..3 {
+                    parser.number().ok_or_else(invalid)?;
+                }
+                parser.flag().ok_or_else(invalid)?;
+                parser.flag().ok_or_else(invalid)?;
+                current = point(&mut parser)?;
+            }
+            b'Z' => current = subpath_start,
+            _ => return Err(invalid()),
+        }
+        last_control = control;
+
+        if let Some(subpath) = subpaths.last_mut() {
+            subpath.push(current);
+        } else {
+            return Err(invalid());
+        }
+
+        // Repeat the command while there are arguments left, otherwise read the next one
+        if let Some(next) = parser.command() {
+            command = next;
+        } else if parser.at_end() {
+            break;
+        } else if command.eq_ignore_ascii_case(&b'Z') {
+            return Err(invalid());
+        }
+    }
+
+    Ok(subpaths)
+}
+
+/// Add a flattened curve, from just after its start to its end, to the current subpath
+fn push_curve(subpaths: &mut [Vec<Vec2>], curve: impl Fn(f32) -> Vec2) {
+    if let Some(subpath) = subpaths.last_mut() {
+        subpath.extend((1..CURVE_SEGMENTS).map(|i| curve(i as f32 / CURVE_SEGMENTS as f32)));
+    }
+}
+
+/// Reads commands and numbers from SVG path data and number lists
+struct PathParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PathParser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|&byte| byte.is_ascii_whitespace() || byte == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.position >= self.bytes.len()
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let byte = *self.bytes.get(self.position)?;
+        if byte.is_ascii_alphabetic() {
+            self.position += 1;
+            Some(byte)
+        } else {
+            None
+        }
+    }
+
+    /// Arc flags can be written without separators, e.g. `a 5 5 0 01 10 10`
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.bytes.get(self.position)? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+
+    /// A number ends at anything that can't continue it, so `1.5.5-2` is three numbers
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            while parser
+                .bytes
+                .get(parser.position)
+                .is_some_and(u8::is_ascii_digit)
+            {
+                parser.position += 1;
+            }
+        };
+        let accept = |parser: &mut Self, options: &[u8]| {
+            let accepted = parser
+                .bytes
+                .get(parser.position)
+                .is_some_and(|byte| options.contains(byte));
+            if accepted {
+                parser.position += 1;
+            }
+            accepted
+        };
+
+        accept(self, b"+-");
+        digits(self);
+        if accept(self, b".") {
+            digits(self);
+        }
+        let mantissa_end = self.position;
+        if accept(self, b"eE") {
+            accept(self, b"+-");
+            let exponent_start = self.position;
+            digits(self);
+            if self.position == exponent_start {
+                self.position = mantissa_end;
+            }
+        }
+
+        let number = std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()?
+            .parse()
+            .ok();
+        if number.is_none() {
+            self.position = start;
+        }
+        number
+    }
+}
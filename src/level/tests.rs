@@ -0,0 +1,1088 @@
+//! Snapshot tests for the polygon extraction and level formats
+//!
+//! Levels use a grid size of 2 so every vertex, including spike tips, lands on a whole number.
+//! Counter-clockwise polygons have a negative winding order.
+
+use bevy::{
+    color::{palettes::css, Color, Srgba},
+    math::Rect,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{
+    ascii::parse_ascii_level,
+    bake::{bake_level, parse_baked_level},
+    bitmap::parse_bitmap_level,
+    fill::{triangulate, wall_fills},
+    generate::{generate_level, generate_tiles, GeneratorKind},
+    svg::{parse_svg_level, polygons_to_svg, read_svg_level},
+    tiled::parse_tiled_map,
+    *,
+};
+
+/// Build a level from rows of tile ID digits
+fn level(rows: &[&str]) -> LevelData {
+    let tiles = rows
+        .iter()
+        .map(|row| {
+            row.chars()
+                .map(|tile| tile.to_digit(10).expect("tile rows are digits"))
+                .collect()
+        })
+        .collect();
+
+    let mut level = LevelData::from_tiles(tiles);
+    level.grid_size = 2.0;
+    level
+}
+
+fn extract(level: &LevelData) -> Vec<Polygon> {
+    let (polygons, _, _) =
+        generate_level_polygons_with_rng(level, &mut StdRng::seed_from_u64(0)).unwrap();
+    polygons
+}
+
+/// Check a polygon's vertices (closed, first point repeated at the end), winding and collision side
+fn assert_polygon(polygon: &Polygon, points: &[[f32; 2]], winding: f32, collision_side: f32) {
+    let actual: Vec<[f32; 2]> = polygon
+        .points
+        .iter()
+        .map(|point| point.to_array())
+        .collect();
+    assert_eq!(actual, points);
+    assert_eq!(calculate_winding_order(&polygon.points).signum(), winding);
+    assert_eq!(polygon.collision_side, collision_side);
+}
+
+#[test]
+fn single_block() {
+    let polygons = extract(&level(&["000", "010", "000"]));
+
+    assert_eq!(polygons.len(), 1);
+    assert_polygon(
+        &polygons[0],
+        &[
+            [-1.0, 1.0],
+            [-1.0, -1.0],
+            [1.0, -1.0],
+            [1.0, 1.0],
+            [-1.0, 1.0],
+        ],
+        -1.0,
+        -1.0,
+    );
+}
+
+#[test]
+fn l_shape_merges_straight_edges() {
+    let polygons = extract(&level(&["0000", "0100", "0110", "0000"]));
+
+    assert_eq!(polygons.len(), 1);
+    assert_polygon(
+        &polygons[0],
+        &[
+            [0.0, 2.0],
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, -2.0],
+            [-2.0, -2.0],
+            [-2.0, 2.0],
+            [0.0, 2.0],
+        ],
+        1.0,
+        1.0,
+    );
+}
+
+#[test]
+fn every_slope_tile() {
+    let cases: [(&str, &[[f32; 2]], f32); 8] = [
+        // Right triangles
+        (
+            "020",
+            &[[-1.0, 1.0], [1.0, -1.0], [-1.0, -1.0], [-1.0, 1.0]],
+            1.0,
+        ),
+        (
+            "030",
+            &[[1.0, 1.0], [-1.0, -1.0], [1.0, -1.0], [1.0, 1.0]],
+            -1.0,
+        ),
+        (
+            "040",
+            &[[-1.0, -1.0], [1.0, 1.0], [-1.0, 1.0], [-1.0, -1.0]],
+            -1.0,
+        ),
+        (
+            "050",
+            &[[1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]],
+            1.0,
+        ),
+        // Spikes
+        (
+            "060",
+            &[[-1.0, -1.0], [0.0, 0.0], [1.0, -1.0], [-1.0, -1.0]],
+            1.0,
+        ),
+        (
+            "070",
+            &[[-1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [-1.0, 1.0]],
+            -1.0,
+        ),
+        (
+            "080",
+            &[[-1.0, 1.0], [0.0, 0.0], [-1.0, -1.0], [-1.0, 1.0]],
+            1.0,
+        ),
+        (
+            "090",
+            &[[1.0, 1.0], [0.0, 0.0], [1.0, -1.0], [1.0, 1.0]],
+            -1.0,
+        ),
+    ];
+
+    for (row, points, winding) in cases {
+        let polygons = extract(&level(&["000", row, "000"]));
+
+        assert_eq!(polygons.len(), 1, "tile row {row}");
+        // Solid polygons collide on their winding side
+        assert_polygon(&polygons[0], points, winding, winding);
+    }
+}
+
+#[test]
+fn container_with_island() {
+    let polygons = extract(&level(&[
+        "11111", //
+        "10001", //
+        "10101", //
+        "10001", //
+        "11111", //
+    ]));
+
+    assert_eq!(polygons.len(), 3);
+    // Island over the origin, nested in the container's hole so still solid
+    assert_polygon(
+        &polygons[0],
+        &[
+            [-1.0, 1.0],
+            [-1.0, -1.0],
+            [1.0, -1.0],
+            [1.0, 1.0],
+            [-1.0, 1.0],
+        ],
+        -1.0,
+        -1.0,
+    );
+    // Outer edge of the container
+    assert_polygon(
+        &polygons[1],
+        &[
+            [-5.0, -5.0],
+            [-5.0, 5.0],
+            [5.0, 5.0],
+            [5.0, -5.0],
+            [-5.0, -5.0],
+        ],
+        1.0,
+        1.0,
+    );
+    // Hole in the container, collides from the inside
+    assert_polygon(
+        &polygons[2],
+        &[
+            [3.0, 3.0],
+            [-3.0, 3.0],
+            [-3.0, -3.0],
+            [3.0, -3.0],
+            [3.0, 3.0],
+        ],
+        -1.0,
+        1.0,
+    );
+}
+
+#[test]
+fn adjacent_slopes_share_merged_edges() {
+    // Two slopes forming a V merge along their bottom edge
+    let polygons = extract(&level(&["0000", "0230", "0000"]));
+
+    assert_eq!(polygons.len(), 1);
+    assert_polygon(
+        &polygons[0],
+        &[
+            [-2.0, 1.0],
+            [0.0, -1.0],
+            [2.0, 1.0],
+            [2.0, -1.0],
+            [-2.0, -1.0],
+            [-2.0, 1.0],
+        ],
+        1.0,
+        1.0,
+    );
+
+    // A diagonal run of slopes keeps a vertex between each hypotenuse
+    let polygons = extract(&level(&["0000", "0200", "0120", "0000"]));
+
+    assert_eq!(polygons.len(), 1);
+    assert_polygon(
+        &polygons[0],
+        &[
+            [-2.0, 2.0],
+            [0.0, 0.0],
+            [2.0, -2.0],
+            [-2.0, -2.0],
+            [-2.0, 2.0],
+        ],
+        1.0,
+        1.0,
+    );
+}
+
+/// Corners of each polygon extracted from `assets/level.json`, sorted
+///
+/// Taken with the pairwise line merging and joining that the end point hash maps replaced.
+const SHIPPED_LEVEL_POLYGONS: &[&[[f32; 2]]] = &[
+    &[
+        [-304.0, -336.0],
+        [-304.0, -240.0],
+        [-304.0, 240.0],
+        [-304.0, 336.0],
+        [-272.0, -208.0],
+        [-272.0, 208.0],
+        [272.0, -208.0],
+        [272.0, 208.0],
+        [304.0, -336.0],
+        [304.0, -240.0],
+        [304.0, 240.0],
+        [304.0, 336.0],
+    ],
+    &[
+        [-240.0, -208.0],
+        [-240.0, -16.0],
+        [-240.0, 112.0],
+        [-240.0, 304.0],
+        [-208.0, -240.0],
+        [-208.0, -208.0],
+        [-208.0, -16.0],
+        [-208.0, 112.0],
+        [-176.0, -272.0],
+        [-144.0, -304.0],
+        [144.0, -304.0],
+        [144.0, -176.0],
+        [240.0, -176.0],
+        [240.0, 304.0],
+    ],
+    &[
+        [-208.0, -176.0],
+        [-208.0, -48.0],
+        [-176.0, -176.0],
+        [-176.0, -80.0],
+        [-176.0, -48.0],
+        [-176.0, 16.0],
+        [-144.0, -80.0],
+        [-144.0, -48.0],
+        [-144.0, 16.0],
+        [-112.0, -48.0],
+        [-112.0, -16.0],
+    ],
+    &[
+        [-112.0, -208.0],
+        [-112.0, -176.0],
+        [-80.0, -208.0],
+        [-80.0, -176.0],
+    ],
+    &[
+        [-112.0, -112.0],
+        [-112.0, -80.0],
+        [-80.0, -112.0],
+        [-80.0, -80.0],
+    ],
+    &[
+        [-16.0, 176.0],
+        [-16.0, 208.0],
+        [16.0, 144.0],
+        [48.0, 144.0],
+        [48.0, 208.0],
+    ],
+    &[
+        [16.0, -208.0],
+        [16.0, -176.0],
+        [48.0, -208.0],
+        [48.0, -176.0],
+    ],
+    &[[16.0, -112.0], [16.0, -80.0], [48.0, -112.0], [48.0, -80.0]],
+    &[
+        [48.0, 16.0],
+        [80.0, -16.0],
+        [80.0, 16.0],
+        [112.0, 48.0],
+        [144.0, -16.0],
+        [144.0, 48.0],
+    ],
+    &[
+        [80.0, 144.0],
+        [80.0, 208.0],
+        [112.0, 144.0],
+        [144.0, 176.0],
+        [144.0, 208.0],
+    ],
+];
+
+#[test]
+fn shipped_level_matches_snapshot() {
+    let polygons = extract(&parse_level_data(LEVEL_DATA).unwrap());
+
+    let mut corners: Vec<Vec<[f32; 2]>> = polygons
+        .iter()
+        .map(|polygon| {
+            let mut points: Vec<[f32; 2]> = polygon
+                .points
+                .iter()
+                .map(|point| point.to_array())
+                .collect();
+            points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            points.dedup();
+            points
+        })
+        .collect();
+    corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    assert_eq!(corners, SHIPPED_LEVEL_POLYGONS);
+}
+
+#[test]
+fn seeded_colors_are_stable() {
+    let level = level(&["11111", "10001", "10101", "10001", "11111"]);
+
+    let colors = |seed| {
+        generate_level_polygons_with_rng(&level, &mut StdRng::seed_from_u64(seed))
+            .unwrap()
+            .0
+            .iter()
+            .map(|polygon| polygon.color.to_srgba())
+            .collect::<Vec<_>>()
+    };
+
+    // Pinned, so a change to the RNG or how it's used shows up as a failure
+    let island = Srgba::rgb(0.30708623, 0.060725927, 0.14264214);
+    let container = Srgba::rgb(0.41664088, 0.030317307, 0.14255321);
+    assert_eq!(colors(7), [island, container, container]);
+    assert_ne!(colors(7), colors(8));
+}
+
+#[test]
+fn level_colors_follow_theme_and_color_seed() {
+    let mut level = level(&["11111", "10001", "10101", "10001", "11111"]);
+    let colors = |level: &LevelData| {
+        generate_level_polygons(level)
+            .unwrap()
+            .0
+            .iter()
+            .map(|polygon| polygon.color)
+            .collect::<Vec<_>>()
+    };
+
+    // Okabe-Ito sky blue and reddish purple, the container and its hole share a colour
+    level.theme = theme::ThemeName::ColorBlind;
+    let island = Color::from(Srgba::rgb_u8(0xcc, 0x79, 0xa7));
+    let container = Color::from(Srgba::rgb_u8(0x56, 0xb4, 0xe9));
+    assert_eq!(colors(&level), [island, container, container]);
+
+    level.color_seed = 3;
+    let palette = level.theme().palette;
+    assert!(colors(&level).iter().all(|color| palette.contains(color)));
+
+    let reloaded = parse_level_data(level.to_json().as_bytes()).unwrap();
+    assert_eq!(reloaded.theme, level.theme);
+    assert_eq!(colors(&reloaded), colors(&level));
+}
+
+#[test]
+fn palette_colors_container_and_holes() {
+    let mut level = level(&["11111", "10001", "10101", "10001", "11111"]);
+    let container = Color::from(css::RED);
+    let island = Color::from(css::BLUE);
+    level.palette = vec![container, island];
+
+    let polygons = extract(&level);
+
+    // The container and its hole share the first palette colour
+    assert_eq!(polygons[1].color, container);
+    assert_eq!(polygons[2].color, container);
+    assert!(level.palette.contains(&polygons[0].color));
+}
+
+#[test]
+fn ascii_level_slopes_and_spawns() {
+    let level = parse_ascii_level(
+        br"
+##########
+#   P    #
+#/      /#
+#\  C  /##
+##########
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        level.tiles,
+        [
+            [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            // Slopes against a wall with nothing below become ceilings
+            [1, 4, 0, 0, 0, 0, 0, 0, 3, 1],
+            [1, 2, 0, 0, 0, 0, 0, 3, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        ]
+    );
+    assert_eq!(level.player_spawn, level.tile_center(4, 1));
+    assert_eq!(level.creature_spawns.len(), 1);
+    assert_eq!(level.creature_spawns[0].name, "C");
+    assert_eq!(level.creature_spawns[0].position, level.tile_center(4, 3));
+}
+
+#[test]
+fn svg_round_trip_keeps_geometry_sides_and_colors() {
+    let polygons = extract(&level(&["1111", "1001", "1021", "1111"]));
+    let svg = polygons_to_svg(&polygons, Vec2::new(8.0, 8.0));
+
+    let imported = parse_svg_level(svg.as_bytes()).unwrap();
+    let (reimported, _, _) =
+        generate_level_polygons_with_rng(&imported, &mut StdRng::seed_from_u64(0)).unwrap();
+
+    assert_eq!(reimported.len(), polygons.len());
+    for (polygon, reimported) in polygons.iter().zip(&reimported) {
+        assert_eq!(reimported.points, polygon.points);
+        assert_eq!(reimported.collision_side, polygon.collision_side);
+        assert_eq!(
+            reimported.color.to_srgba().to_hex(),
+            polygon.color.to_srgba().to_hex()
+        );
+    }
+}
+
+#[test]
+fn svg_paths_use_transforms_and_flatten_curves() {
+    let level = parse_svg_level(
+        br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+  <g transform="translate(10 0)">
+    <path style="fill:none;stroke:#ff0000" d="m -10,0 h 20 v 20 H -10 z M -5 5 h 4 q 0 5 -4 5 z"/>
+  </g>
+</svg>"##,
+    )
+    .unwrap();
+
+    assert_eq!(level.polygons.len(), 2);
+    assert_eq!(
+        level.polygons[0].points,
+        [
+            Vec2::new(-10.0, 10.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(10.0, -10.0),
+            Vec2::new(-10.0, -10.0),
+            Vec2::new(-10.0, 10.0),
+        ]
+    );
+    assert_eq!(level.polygons[0].color, Some(Color::srgb(1.0, 0.0, 0.0)));
+    assert_eq!(level.polygons[0].collision_side, None);
+    // Two straight edges and a curve flattened into 8 segments
+    assert_eq!(level.polygons[1].points.len(), 11);
+}
+
+#[test]
+fn svg_drawing_after_close_starts_a_new_subpath() {
+    let level = read_svg_level(
+        br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+  <path d="M 10 10 h -4 v -4 z l 4 0 v 4 z"/>
+</svg>"##,
+    )
+    .unwrap();
+
+    let points: Vec<&[Vec2]> = level
+        .polygons
+        .iter()
+        .map(|polygon| polygon.points.as_slice())
+        .collect();
+    assert_eq!(
+        points,
+        [
+            &[
+                Vec2::new(0.0, 0.0),
+                Vec2::new(-4.0, 0.0),
+                Vec2::new(-4.0, 4.0),
+                Vec2::new(0.0, 0.0),
+            ][..],
+            &[
+                Vec2::new(0.0, 0.0),
+                Vec2::new(4.0, 0.0),
+                Vec2::new(4.0, -4.0),
+                Vec2::new(0.0, 0.0),
+            ][..],
+        ]
+    );
+}
+
+/// Encode RGBA pixel rows as a PNG
+fn encode_png(rows: &[&[[u8; 4]]]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, rows[0].len() as u32, rows.len() as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&rows.concat().concat()).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+#[test]
+fn bitmap_level_tiles_and_spawns() {
+    const W: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+    const K: [u8; 4] = [0x00, 0x00, 0x00, 0xff];
+    const R: [u8; 4] = [0xff, 0x00, 0x00, 0xff];
+    const M: [u8; 4] = [0xff, 0x00, 0xff, 0xff];
+    const P: [u8; 4] = [0x00, 0xff, 0xff, 0xff];
+    const C: [u8; 4] = [0xff, 0xff, 0x00, 0xff];
+    const CLEAR: [u8; 4] = [0x12, 0x34, 0x56, 0x00];
+
+    let level = parse_bitmap_level(&encode_png(&[
+        &[K, K, K, K],
+        &[K, P, M, K],
+        &[K, R, C, K],
+        &[K, CLEAR, W, K],
+    ]))
+    .unwrap();
+
+    assert_eq!(
+        level.tiles,
+        [[1, 1, 1, 1], [1, 0, 5, 1], [1, 2, 0, 1], [1, 0, 0, 1]]
+    );
+    assert_eq!(level.player_spawn, level.tile_center(1, 1));
+    assert_eq!(level.creature_spawns.len(), 1);
+    assert_eq!(level.creature_spawns[0].position, level.tile_center(2, 2));
+
+    let err = parse_bitmap_level(&encode_png(&[&[K, [0x80, 0x80, 0x80, 0xff]]])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown pixel colour #808080 at row 0, column 1"
+    );
+}
+
+fn triangles_area(triangles: &[[Vec2; 3]]) -> f32 {
+    triangles
+        .iter()
+        .map(|[a, b, c]| (*b - *a).perp_dot(*c - *a).abs() / 2.0)
+        .sum()
+}
+
+#[test]
+fn wall_fills_cover_solid_area_only() {
+    // Container ring (10x10 minus the 6x6 hole) and the 2x2 island in the hole
+    let polygons = extract(&level(&["11111", "10001", "10101", "10001", "11111"]));
+    let fills = wall_fills(&polygons, Rect::default());
+
+    assert_eq!(fills.len(), 2);
+    let areas: Vec<f32> = fills
+        .iter()
+        .map(|fill| triangles_area(&fill.triangles))
+        .collect();
+    assert_eq!(areas, [4.0, 64.0]);
+
+    // Slopes and a concave outline, with a collinear run along the diagonal
+    let polygons = extract(&level(&["00000", "02000", "01200", "01110", "00000"]));
+    let fills = wall_fills(&polygons, Rect::default());
+    // Four squares and two half squares
+    assert_eq!(triangles_area(&fills[0].triangles), 4.0 * 4.0 + 2.0 * 2.0);
+}
+
+#[test]
+fn triangulate_bridges_several_holes() {
+    let square = |center: Vec2, half: f32| {
+        vec![
+            center + Vec2::new(-half, -half),
+            center + Vec2::new(half, -half),
+            center + Vec2::new(half, half),
+            center + Vec2::new(-half, half),
+        ]
+    };
+
+    let triangles = triangulate(
+        &square(Vec2::ZERO, 10.0),
+        &[
+            square(Vec2::new(-5.0, 0.0), 2.0),
+            square(Vec2::new(5.0, 0.0), 2.0),
+            square(Vec2::new(5.0, 5.0), 1.0),
+        ],
+    );
+
+    assert_eq!(triangles_area(&triangles), 400.0 - 16.0 - 16.0 - 4.0);
+}
+
+#[test]
+fn baked_level_matches_runtime_extraction() {
+    let mut level = level(&["11111", "10001", "10201", "10001", "11111"]);
+    let baked = parse_baked_level(&bake_level(&level).unwrap()).unwrap();
+
+    let (polygons, size, half_size) = generate_level_polygons(&level).unwrap();
+    assert!(baked.is_baked_from(&level));
+    assert_eq!((baked.size, baked.half_size), (size, half_size));
+    assert_eq!(baked.polygons.len(), polygons.len());
+    for (baked, polygon) in baked.polygons.iter().zip(&polygons) {
+        assert_eq!(baked.points, polygon.points);
+        assert_eq!(baked.collision_side, polygon.collision_side);
+        assert_eq!(baked.color, polygon.color);
+    }
+
+    let cell_edges = |grid: &crate::spatial::SpatialGrid| {
+        let mut cells: Vec<_> = grid
+            .cells()
+            .map(|(cell, edge_ids)| {
+                let edges = edge_ids
+                    .iter()
+                    .map(|&id| {
+                        let edge = grid.edge(id);
+                        [edge.start.x, edge.start.y, edge.end.x, edge.end.y]
+                    })
+                    .collect::<Vec<_>>();
+                (cell, edges)
+            })
+            .collect();
+        cells.sort_by_key(|(cell, _)| *cell);
+        cells
+    };
+    assert_eq!(
+        cell_edges(&baked.spatial_grid()),
+        cell_edges(&crate::spatial::SpatialGrid::new(
+            &polygons,
+            level.grid_size
+        ))
+    );
+
+    // Any edit makes the blob stale
+    level.tiles[2][2] = 1;
+    assert!(!baked.is_baked_from(&level));
+    assert!(parse_baked_level(b"FLEEBAKE").is_err());
+}
+
+#[test]
+fn baked_level_with_other_version_or_hash_is_rejected() {
+    let level = level(&["111", "101", "111"]);
+    let blob = bake_level(&level).unwrap();
+
+    // Version follows the 8 byte magic, then the source hash
+    let mut other_version = blob.clone();
+    let version = u32::from_le_bytes(other_version[8..12].try_into().unwrap());
+    other_version[8..12].copy_from_slice(&(version + 1).to_le_bytes());
+    assert!(matches!(
+        parse_baked_level(&other_version),
+        Err(LevelError::InvalidBake(_))
+    ));
+
+    let mut other_hash = blob;
+    other_hash[12] ^= 1;
+    let baked = parse_baked_level(&other_hash).unwrap();
+    assert!(!baked.is_baked_from(&level));
+}
+
+#[test]
+fn generated_levels_are_reproducible_and_connected() {
+    for kind in [GeneratorKind::Caves, GeneratorKind::Rooms] {
+        for (width, height) in [
+            (3, 3),
+            (4, 4),
+            (5, 5),
+            (8, 5),
+            (10, 10),
+            (20, 20),
+            (40, 25),
+            (13, 60),
+        ] {
+            for seed in 0..20 {
+                let level = generate_level(kind, width, height, seed, 2.0);
+                assert_eq!(level.tiles, generate_tiles(kind, width, height, seed));
+                generate_level_polygons(&level)
+                    .unwrap_or_else(|err| panic!("{kind:?} {width}x{height} seed {seed}: {err}"));
+
+                // Flood fill the empty tiles from the player spawn
+                let tiles = &level.tiles;
+                let (width, height) = (tiles[0].len(), tiles.len());
+                assert!(
+                    tiles.iter().flatten().any(|&tile| tile == 0),
+                    "{kind:?} {width}x{height} seed {seed}: no open tiles"
+                );
+                let spawn_tile = level.world_to_tile(level.player_spawn);
+                assert!(
+                    spawn_tile.is_some_and(|(x, y)| tiles[y][x] == 0),
+                    "{kind:?} {width}x{height} seed {seed}: player spawns in a wall"
+                );
+                for spawn in &level.creature_spawns {
+                    assert!(
+                        level
+                            .world_to_tile(spawn.position)
+                            .is_some_and(|(x, y)| tiles[y][x] == 0),
+                        "{kind:?} {width}x{height} seed {seed}: creature spawns in a wall"
+                    );
+                }
+                let mut reached = vec![vec![false; width]; height];
+                let mut stack = Vec::from_iter(spawn_tile);
+                while let Some((x, y)) = stack.pop() {
+                    if tiles[y][x] != 0 || std::mem::replace(&mut reached[y][x], true) {
+                        continue;
+                    }
+                    stack.extend([(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]);
+                }
+
+                for (y, row) in tiles.iter().enumerate() {
+                    for (x, &tile) in row.iter().enumerate() {
+                        assert!(
+                            tile != 0 || reached[y][x],
+                            "{kind:?} {width}x{height} seed {seed}: tile ({x}, {y}) unreachable"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Too small levels are grown instead of panicking
+    for kind in [GeneratorKind::Caves, GeneratorKind::Rooms] {
+        let tiles = generate_tiles(kind, 0, 1, 0);
+        assert_eq!((tiles[0].len(), tiles.len()), (3, 3));
+    }
+}
+
+/// A 16 pixel Tiled map with one tile layer and one object layer
+fn tiled_map(
+    (width, height): (usize, usize),
+    data: &[u32],
+    tilesets: serde_json::Value,
+    objects: serde_json::Value,
+) -> Vec<u8> {
+    serde_json::json!({
+        "width": width,
+        "height": height,
+        "tilewidth": 16,
+        "tileheight": 16,
+        "layers": [
+            { "type": "tilelayer", "name": "walls", "data": data },
+            { "type": "group", "layers": [{ "type": "objectgroup", "objects": objects }] },
+        ],
+        "tilesets": tilesets,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+#[test]
+fn tiled_gids_map_to_tile_ids() {
+    // The second tileset's first tile is a spike through its tile_id property
+    let tilesets = serde_json::json!([
+        { "firstgid": 1 },
+        { "firstgid": 10, "tiles": [
+            { "id": 0, "properties": [{ "name": "tile_id", "type": "int", "value": 6 }] },
+        ] },
+    ]);
+    let map = tiled_map((5, 1), &[0, 1, 3, 10, 11], tilesets, serde_json::json!([]));
+    assert_eq!(parse_tiled_map(&map).unwrap().tiles, [[0, 1, 3, 6, 2]]);
+
+    // Tiles count from the tileset's first GID
+    let map = tiled_map(
+        (3, 1),
+        &[0, 5, 9],
+        serde_json::json!([{ "firstgid": 5 }]),
+        serde_json::json!([]),
+    );
+    assert_eq!(parse_tiled_map(&map).unwrap().tiles, [[0, 1, 5]]);
+}
+
+#[test]
+fn tiled_flip_flags_turn_slopes_and_spikes() {
+    const HORIZONTAL: u32 = 0x8000_0000;
+    const VERTICAL: u32 = 0x4000_0000;
+    const DIAGONAL: u32 = 0x2000_0000;
+
+    // Expected tile for each of tiles 2-9 flipped
+    let cases = [
+        (HORIZONTAL, [3, 2, 5, 4, 6, 7, 9, 8]),
+        (VERTICAL, [4, 5, 2, 3, 7, 6, 8, 9]),
+        (DIAGONAL, [5, 3, 4, 2, 9, 8, 7, 6]),
+        // Rotated 90 degrees clockwise
+        (DIAGONAL | HORIZONTAL, [4, 2, 5, 3, 8, 9, 7, 6]),
+    ];
+    for (flags, expected) in cases {
+        let data: Vec<u32> = (2..=9).map(|tile| tile | flags).collect();
+        let map = tiled_map(
+            (8, 1),
+            &data,
+            serde_json::json!([{ "firstgid": 1 }]),
+            serde_json::json!([]),
+        );
+        assert_eq!(
+            parse_tiled_map(&map).unwrap().tiles,
+            [expected],
+            "flags {flags:#x}"
+        );
+    }
+}
+
+#[test]
+fn tiled_objects_become_spawns_and_triggers() {
+    let objects = serde_json::json!([
+        { "type": "player", "x": 16, "y": 0, "width": 16, "height": 16 },
+        { "class": "creature", "name": "creature_a", "x": 8, "y": 24 },
+        { "type": "trigger", "name": "exit", "x": 0, "y": 0, "width": 32, "height": 16 },
+        { "type": "decoration", "x": 0, "y": 0 },
+    ]);
+    let map = tiled_map((4, 2), &[0; 8], serde_json::json!([]), objects);
+    let level = parse_tiled_map(&map).unwrap();
+
+    // The map's top left is at (-32, 16)
+    assert_eq!(level.grid_size, 16.0);
+    assert_eq!(level.player_spawn, Vec2::new(-8.0, 8.0));
+    assert_eq!(level.creature_spawns.len(), 1);
+    assert_eq!(level.creature_spawns[0].name, "creature_a");
+    assert_eq!(level.creature_spawns[0].position, Vec2::new(-24.0, -8.0));
+    assert_eq!(level.triggers.len(), 1);
+    assert_eq!(level.triggers[0].name, "exit");
+    assert_eq!(
+        level.triggers[0].rect,
+        Rect::from_corners(Vec2::new(-32.0, 16.0), Vec2::new(0.0, 0.0))
+    );
+}
+
+#[test]
+fn tiled_unsupported_maps_are_errors() {
+    let map = |change: fn(&mut serde_json::Value)| {
+        let mut map: serde_json::Value = serde_json::from_slice(&tiled_map(
+            (1, 1),
+            &[1],
+            serde_json::json!([]),
+            serde_json::json!([]),
+        ))
+        .unwrap();
+        change(&mut map);
+        parse_tiled_map(map.to_string().as_bytes())
+    };
+
+    assert!(map(|_| {}).is_ok());
+    assert!(matches!(
+        map(|map| {
+            map["layers"][0]["encoding"] = "base64".into();
+            map["layers"][0]["data"] = "AQAAAA==".into();
+        }),
+        Err(LevelError::UnsupportedTiledMap(_))
+    ));
+    assert!(matches!(
+        map(|map| map["tileheight"] = 8.into()),
+        Err(LevelError::UnsupportedTiledMap(_))
+    ));
+}
+
+#[test]
+fn level_json_round_trips_and_escapes_names() {
+    let mut level = level(&["111", "101", "111"]);
+    level.creature_spawns[0].name = "quote \" and \\ backslash".to_string();
+    level.palette = vec![Color::from(css::RED)];
+    level.triggers.push(format::TriggerArea {
+        name: "exit\n".to_string(),
+        rect: Rect::new(-1.0, -2.0, 1.0, 2.0),
+    });
+    level.polygons.push(shapes::LevelPolygon {
+        points: vec![
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(-1.0, 0.0),
+        ],
+        collision_side: Some(shapes::CollisionSide::Outside),
+        color: Some(Color::from(css::BLUE)),
+    });
+
+    let reloaded = parse_level_data(level.to_json().as_bytes()).unwrap();
+    assert_eq!(reloaded.tiles, level.tiles);
+    assert_eq!(reloaded.grid_size, level.grid_size);
+    assert_eq!(reloaded.creature_spawns, level.creature_spawns);
+    assert_eq!(reloaded.palette, level.palette);
+    assert_eq!(reloaded.triggers, level.triggers);
+    assert_eq!(reloaded.polygons, level.polygons);
+}
+
+fn free_polygon(points: &[[f32; 2]]) -> shapes::LevelPolygon {
+    shapes::LevelPolygon {
+        points: points.iter().copied().map(Vec2::from).collect(),
+        collision_side: None,
+        color: None,
+    }
+}
+
+#[test]
+fn free_polygons_are_validated() {
+    let validate = |polygons: &[shapes::LevelPolygon]| shapes::validate_level_polygons(polygons);
+    let square = |min: f32, size: f32| {
+        let max = min + size;
+        free_polygon(&[[min, min], [max, min], [max, max], [min, max], [min, min]])
+    };
+
+    assert!(validate(&[square(0.0, 4.0), square(1.0, 2.0)]).is_ok());
+    assert!(matches!(
+        validate(&[free_polygon(&[[0.0, 0.0], [1.0, 0.0], [0.0, 0.0]])]),
+        Err(LevelError::TooFewPoints {
+            polygon: 0,
+            points: 2
+        })
+    ));
+    assert!(matches!(
+        validate(&[free_polygon(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0]
+        ])]),
+        Err(LevelError::UnclosedPolygon { .. })
+    ));
+    assert!(matches!(
+        validate(&[free_polygon(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 0.0]
+        ])]),
+        Err(LevelError::ZeroLengthEdge { polygon: 0, .. })
+    ));
+    // A bow tie
+    assert!(matches!(
+        validate(&[
+            square(5.0, 1.0),
+            free_polygon(&[[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0], [0.0, 0.0]])
+        ]),
+        Err(LevelError::SelfIntersectingPolygon { polygon: 1, point }) if point == Vec2::ONE
+    ));
+    assert!(matches!(
+        validate(&[square(0.0, 2.0), square(1.0, 2.0)]),
+        Err(LevelError::IntersectingPolygons {
+            first: 0,
+            second: 1,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn free_polygons_crossing_tile_walls_are_rejected() {
+    // The open tile spans (-1, -1) to (1, 1)
+    let mut level = level(&["111", "101", "111"]);
+    level.polygons = vec![free_polygon(&[
+        [-0.5, -0.5],
+        [0.5, -0.5],
+        [0.5, 0.5],
+        [-0.5, -0.5],
+    ])];
+    let (polygons, _, _) = generate_level_polygons(&level).unwrap();
+
+    // A rock in the room's open space is solid inside
+    let solid_inside = level.polygons_solid_inside(&polygons);
+    assert_eq!(solid_inside, [true]);
+    assert!(level.is_solid_at(Vec2::new(1.0, -1.0) / 6.0, &solid_inside));
+
+    // and a polygon nested in it is open again
+    let mut nested = level.clone();
+    nested.polygons = vec![
+        free_polygon(&[
+            [-0.8, -0.8],
+            [0.8, -0.8],
+            [0.8, 0.8],
+            [-0.8, 0.8],
+            [-0.8, -0.8],
+        ]),
+        free_polygon(&[
+            [-0.4, -0.4],
+            [0.4, -0.4],
+            [0.4, 0.4],
+            [-0.4, 0.4],
+            [-0.4, -0.4],
+        ]),
+    ];
+    let solid_inside = nested.polygons_solid_inside(&extract(&nested));
+    assert_eq!(solid_inside, [true, false]);
+    assert!(nested.is_solid_at(Vec2::new(0.6, 0.0), &solid_inside));
+    assert!(!nested.is_solid_at(Vec2::ZERO, &solid_inside));
+
+    level.polygons[0].points[1] = Vec2::new(2.5, -0.5);
+    assert!(matches!(
+        generate_level_polygons(&level),
+        Err(LevelError::PolygonCrossesTiles { polygon: 0, .. })
+    ));
+
+    // Validating reports the crossing alongside the problems parsing stops at
+    level
+        .polygons
+        .push(free_polygon(&[[0.2, 0.0], [0.3, 0.0], [0.2, 0.0]]));
+    assert!(matches!(
+        validate_level(&level).err().as_deref(),
+        Some([
+            LevelError::TooFewPoints { polygon: 1, .. },
+            LevelError::PolygonCrossesTiles { polygon: 0, .. },
+        ])
+    ));
+}
+
+#[test]
+fn reloaded_levels_move_agents_out_of_solid_tiles() {
+    // Open tiles centred on (-2, 0), (0, 0) and (2, 0)
+    let mut level = level(&["11111", "10001", "11111"]);
+    let agent = Vec2::new(0.3, 0.0);
+    assert!(!level.is_solid_at(agent, &[]));
+
+    level.tiles[1][2] = 1;
+    assert!(level.is_solid_at(agent, &[]));
+    assert_eq!(
+        level.nearest_open_position(agent, &[]),
+        Some(Vec2::new(2.0, 0.0))
+    );
+}
+
+#[test]
+fn reloaded_levels_move_agents_out_of_solid_polygons() {
+    let mut room = level(&["11111", "10001", "11111"]);
+    room.polygons = vec![shapes::LevelPolygon {
+        collision_side: Some(shapes::CollisionSide::Outside),
+        ..free_polygon(&[
+            [-2.5, -0.5],
+            [-1.5, -0.5],
+            [-1.5, 0.5],
+            [-2.5, 0.5],
+            [-2.5, -0.5],
+        ])
+    }];
+    let solid_inside = room.polygons_solid_inside(&extract(&room));
+    assert_eq!(solid_inside, [true]);
+
+    // The rock covers the centre of the left tile, so the middle one is nearest
+    let agent = Vec2::new(-2.0, 0.2);
+    assert!(room.is_solid_at(agent, &solid_inside));
+    assert!(!room.is_solid_at(Vec2::new(-2.8, 0.0), &solid_inside));
+    assert_eq!(
+        room.nearest_open_position(agent, &solid_inside),
+        Some(Vec2::ZERO)
+    );
+
+    // Nowhere to go once the rock covers the only open tile
+    let mut covered = level(&["111", "101", "111"]);
+    covered.polygons = vec![shapes::LevelPolygon {
+        collision_side: Some(shapes::CollisionSide::Outside),
+        ..free_polygon(&[
+            [-0.5, -0.5],
+            [0.5, -0.5],
+            [0.5, 0.5],
+            [-0.5, 0.5],
+            [-0.5, -0.5],
+        ])
+    }];
+    let solid_inside = covered.polygons_solid_inside(&extract(&covered));
+    assert_eq!(
+        covered.nearest_open_position(Vec2::ZERO, &solid_inside),
+        None
+    );
+}
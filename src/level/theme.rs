@@ -0,0 +1,64 @@
+use bevy::color::{Color, Mix, Srgba};
+use serde::{Deserialize, Serialize};
+
+/// Built-in themes a level can pick by name
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    /// Random wall colours, red fleeing and green wandering creatures
+    #[default]
+    Classic,
+    /// The Okabe-Ito palette, which stays distinguishable with colour vision deficiencies
+    ColorBlind,
+}
+
+/// Colours a level is drawn with
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelTheme {
+    /// Wall colours, the first is used for the outer container. Empty for random colours.
+    pub palette: Vec<Color>,
+    /// Creature colour when purely fleeing
+    pub flee_color: Color,
+    /// Creature colour when purely wandering
+    pub wander_color: Color,
+}
+
+impl LevelTheme {
+    pub fn builtin(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Classic => Self {
+                palette: Vec::new(),
+                flee_color: Color::srgb(1.0, 0.0, 0.0),
+                wander_color: Color::srgb(0.0, 1.0, 0.0),
+            },
+            ThemeName::ColorBlind => Self {
+                palette: [
+                    Srgba::rgb_u8(0x56, 0xb4, 0xe9),
+                    Srgba::rgb_u8(0xe6, 0x9f, 0x00),
+                    Srgba::rgb_u8(0x00, 0x9e, 0x73),
+                    Srgba::rgb_u8(0xf0, 0xe4, 0x42),
+                    Srgba::rgb_u8(0xcc, 0x79, 0xa7),
+                ]
+                .into_iter()
+                .map(Color::Srgba)
+                .collect(),
+                flee_color: Srgba::rgb_u8(0xd5, 0x5e, 0x00).into(),
+                wander_color: Srgba::rgb_u8(0x00, 0x72, 0xb2).into(),
+            },
+        }
+    }
+
+    /// Creature colour for a blend between fleeing (0) and wandering (1)
+    pub fn creature_color(&self, blend: f32) -> Color {
+        self.flee_color
+            .to_srgba()
+            .mix(&self.wander_color.to_srgba(), blend)
+            .into()
+    }
+}
+
+impl Default for LevelTheme {
+    fn default() -> Self {
+        Self::builtin(ThemeName::default())
+    }
+}
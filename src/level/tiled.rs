@@ -0,0 +1,296 @@
+use bevy::math::{Rect, Vec2};
+use serde::Deserialize;
+
+use super::{
+    check_level,
+    format::{CreatureSpawn, TriggerArea},
+    LevelData, LevelError,
+};
+
+/// Extension of Tiled JSON maps
+pub const TILED_MAP_EXTENSION: &str = "tmj";
+
+// GID flip flags, see https://doc.mapeditor.org/en/stable/reference/global-tile-ids/
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+const GID_FLAGS: u32 =
+    FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120;
+
+/// Tile property that overrides which tile ID a tileset tile maps to
+const TILE_ID_PROPERTY: &str = "tile_id";
+
+// Object types (classes) read from object layers
+const PLAYER_OBJECT: &str = "player";
+const CREATURE_OBJECT: &str = "creature";
+const TRIGGER_OBJECT: &str = "trigger";
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Deserialize)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    objects: Vec<TiledObject>,
+    /// Child layers of a group layer
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+    #[serde(default)]
+    name: String,
+    /// Called `class` by Tiled 1.9
+    #[serde(default, rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    /// Only present for embedded tilesets
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// Parse a Tiled JSON map (`.tmj`) into level data
+///
+/// * The first tile layer (searching inside groups) is the tile grid, and must use the CSV
+///   layer format. Tileset tiles map to tile IDs in order, so the first tile is 1 (square),
+///   unless the tile has an integer `tile_id` property. Flipped and rotated slope and spike
+///   tiles become the matching tile ID.
+/// * Objects with the type `player` and `creature` are spawn points, at the centre of the
+///   object. Creatures are named after the object.
+/// * Objects with the type `trigger` are trigger areas.
+///
+/// The grid size is the map's tile size, so object positions carry over unscaled.
+pub fn parse_tiled_map(map_data: &[u8]) -> Result<LevelData, LevelError> {
+    read_tiled_map(map_data).and_then(check_level)
+}
+
+/// Same as `parse_tiled_map`, without checking the tile grid
+pub fn read_tiled_map(map_data: &[u8]) -> Result<LevelData, LevelError> {
+    let json_str = std::str::from_utf8(map_data).map_err(LevelError::InvalidUtf8)?;
+    let map: TiledMap = serde_json::from_str(json_str).map_err(LevelError::InvalidJson)?;
+
+    if map.infinite {
+        return Err(LevelError::UnsupportedTiledMap(
+            "infinite maps aren't supported".to_string(),
+        ));
+    }
+    if map.tilewidth != map.tileheight {
+        return Err(LevelError::UnsupportedTiledMap(format!(
+            "tiles must be square, got {}x{}",
+            map.tilewidth, map.tileheight
+        )));
+    }
+    if map.tilewidth.is_nan() || map.tilewidth <= 0.0 {
+        return Err(LevelError::InvalidGridSize(map.tilewidth));
+    }
+
+    let layers = flatten_layers(&map.layers);
+
+    let tile_layer = layers
+        .iter()
+        .find(|layer| layer.kind == "tilelayer")
+        .ok_or_else(|| LevelError::UnsupportedTiledMap("no tile layer".to_string()))?;
+    let gids = tile_layer_gids(tile_layer)?;
+    if gids.len() != map.width * map.height {
+        return Err(LevelError::UnsupportedTiledMap(format!(
+            "layer \"{}\" has {} tiles, expected {}x{}",
+            tile_layer.name,
+            gids.len(),
+            map.width,
+            map.height
+        )));
+    }
+
+    let tiles: Vec<Vec<u32>> = gids
+        .chunks(map.width.max(1))
+        .map(|row| {
+            row.iter()
+                .map(|&gid| gid_to_tile(gid, &map.tilesets))
+                .collect()
+        })
+        .collect();
+
+    let mut level = LevelData::from_tiles(tiles);
+    level.grid_size = map.tilewidth;
+    level.player_spawn = Vec2::ZERO;
+    level.creature_spawns.clear();
+
+    // Tiled positions are in pixels from the top left of the map, Y down
+    let origin = level.origin();
+    let to_world = |x: f32, y: f32| origin + Vec2::new(x, -y);
+
+    for object in layers
+        .iter()
+        .filter(|layer| layer.kind == "objectgroup")
+        .flat_map(|layer| &layer.objects)
+    {
+        let center = to_world(
+            object.x + object.width / 2.0,
+            object.y + object.height / 2.0,
+        );
+
+        match object.kind.as_str() {
+            PLAYER_OBJECT => level.player_spawn = center,
+            CREATURE_OBJECT => level.creature_spawns.push(CreatureSpawn {
+                name: object.name.clone(),
+                position: center,
+            }),
+            TRIGGER_OBJECT => level.triggers.push(TriggerArea {
+                name: object.name.clone(),
+                rect: Rect::from_corners(
+                    to_world(object.x, object.y),
+                    to_world(object.x + object.width, object.y + object.height),
+                ),
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(level)
+}
+
+/// All layers in draw order, with group layers replaced by their children
+fn flatten_layers(layers: &[TiledLayer]) -> Vec<&TiledLayer> {
+    layers
+        .iter()
+        .flat_map(|layer| {
+            if layer.kind == "group" {
+                flatten_layers(&layer.layers)
+            } else {
+                vec![layer]
+            }
+        })
+        .collect()
+}
+
+fn tile_layer_gids(layer: &TiledLayer) -> Result<Vec<u32>, LevelError> {
+    if let Some(encoding) = layer
+        .encoding
+        .as_deref()
+        .filter(|&encoding| encoding != "csv")
+    {
+        return Err(LevelError::UnsupportedTiledMap(format!(
+            "layer \"{}\" uses {encoding} encoding, save it with the CSV layer format",
+            layer.name
+        )));
+    }
+
+    match &layer.data {
+        Some(data) => serde_json::from_value(data.clone()).map_err(LevelError::InvalidJson),
+        None => Err(LevelError::UnsupportedTiledMap(format!(
+            "layer \"{}\" has no tile data, chunked layers aren't supported",
+            layer.name
+        ))),
+    }
+}
+
+/// Map a Tiled global tile ID to a tile ID, leaving unknown IDs for validation to report
+fn gid_to_tile(gid: u32, tilesets: &[TiledTileset]) -> u32 {
+    let flags = gid & GID_FLAGS;
+    let gid = gid & !GID_FLAGS;
+    if gid == 0 {
+        return 0;
+    }
+
+    // The tileset with the highest first GID not above this one
+    let Some(tileset) = tilesets
+        .iter()
+        .filter(|tileset| tileset.firstgid <= gid)
+        .max_by_key(|tileset| tileset.firstgid)
+    else {
+        return gid;
+    };
+    let local_id = gid - tileset.firstgid;
+
+    let mut tile = tileset
+        .tiles
+        .iter()
+        .find(|tile| tile.id == local_id)
+        .and_then(|tile| {
+            tile.properties
+                .iter()
+                .find(|property| property.name == TILE_ID_PROPERTY)
+        })
+        .and_then(|property| property.value.as_u64())
+        .map_or(local_id + 1, |tile| tile as u32);
+
+    // Tiled flips diagonally first, then horizontally, then vertically
+    if flags & FLIPPED_DIAGONALLY != 0 {
+        tile = match tile {
+            2 => 5,
+            5 => 2,
+            6 => 9,
+            9 => 6,
+            7 => 8,
+            8 => 7,
+            _ => tile,
+        };
+    }
+    if flags & FLIPPED_HORIZONTALLY != 0 {
+        tile = match tile {
+            2 => 3,
+            3 => 2,
+            4 => 5,
+            5 => 4,
+            8 => 9,
+            9 => 8,
+            _ => tile,
+        };
+    }
+    if flags & FLIPPED_VERTICALLY != 0 {
+        tile = match tile {
+            2 => 4,
+            4 => 2,
+            3 => 5,
+            5 => 3,
+            6 => 7,
+            7 => 6,
+            _ => tile,
+        };
+    }
+
+    tile
+}
@@ -1,6 +1,10 @@
 mod ai;
+mod camera;
+mod cli;
 mod collisions;
+mod editor;
 mod level;
+mod render;
 mod spatial;
 mod utils;
 
@@ -8,14 +12,47 @@ use std::f32::consts::PI;
 
 use ::bevy::prelude::*;
 use ai::flee::{render_flee_ai, FleeAI, FleeAIPlugin};
-use bevy::{app::AppExit, color::palettes::css, window::PresentMode};
+use bevy::{
+    app::AppExit,
+    asset::{AssetLoadError, AssetLoadFailedEvent, LoadState},
+    color::palettes::css,
+    ecs::system::SystemParam,
+    window::PresentMode,
+};
+use camera::FollowCameraPlugin;
 use collisions::{s_collision, CollisionPlugin};
-use level::{generate_level_polygons, Polygon};
+use editor::EditorPlugin;
+use level::{
+    asset::{
+        baked_level_path, level_asset_path, level_asset_source, BakedLevelLoader, LevelLoader,
+        LEVEL_ASSET_SOURCE,
+    },
+    bake::BakedLevel,
+    format::{TriggerArea, DEFAULT_GRID_SIZE},
+    generate_level_polygons, parse_level_data,
+    theme::LevelTheme,
+    LevelData, Polygon, DEFAULT_LEVEL_PATH, LEVEL_DATA,
+};
+use render::WallRenderPlugin;
 use spatial::SpatialGrid;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(exit_code) = cli::run_subcommand(&args) {
+        std::process::exit(exit_code);
+    }
+
+    // Optional level file path as the first argument
+    let level_path = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_LEVEL_PATH.to_string());
+
     App::new()
+        // Level files get their own asset source so they can live outside `assets/`
+        .register_asset_source(LEVEL_ASSET_SOURCE, level_asset_source(&level_path))
         .insert_resource(ClearColor(Color::srgb(0.0, 0.0, 0.0)))
+        .insert_resource(LevelPath { path: level_path })
         .insert_resource(InputDir { dir: Vec2::ZERO })
         .insert_resource(PlayerPosition {
             position: Vec2::ZERO,
@@ -30,11 +67,24 @@ fn main() {
             }),
             ..default()
         }))
+        .init_asset::<LevelData>()
+        .init_asset_loader::<LevelLoader>()
+        .init_asset::<BakedLevel>()
+        .init_asset_loader::<BakedLevelLoader>()
         .add_plugins(CollisionPlugin)
         .add_plugins(FleeAIPlugin)
+        .add_plugins(EditorPlugin)
+        .add_plugins(WallRenderPlugin)
+        .add_plugins(FollowCameraPlugin)
         // Startup systems
         .add_systems(Startup, s_init)
         // Update systems
+        .add_systems(
+            Update,
+            (s_level_load_failed, s_apply_level, s_place_agents)
+                .chain()
+                .before(s_player_movement),
+        )
         .add_systems(Update, s_input)
         .add_systems(Update, s_player_movement.before(s_collision))
         .add_systems(Update, s_render.after(s_collision))
@@ -45,16 +95,33 @@ pub const PLAYER_MAX_SPEED: f32 = 5.0;
 pub const PLAYER_STEERING_SCALE: f32 = 0.1;
 pub const PLAYER_RADIUS: f32 = 12.0;
 pub const AI_RADIUS: f32 = 8.0;
-pub const AI_SPAWN_POSITION: Vec2 = Vec2::new(100.0, 100.0);
+
+/// Level paths deployed with a `.bake` file next to them on the web
+#[cfg(target_arch = "wasm32")]
+const WEB_BAKED_LEVELS: &[&str] = &[];
 
 #[derive(Resource)]
 pub struct Level {
     pub polygons: Vec<Polygon>,
+    pub triggers: Vec<TriggerArea>,
+    pub theme: LevelTheme,
     pub grid_size: f32,
     pub size: Vec2,
     pub half_size: Vec2,
 }
 
+#[derive(Resource)]
+pub struct LevelPath {
+    pub path: String,
+}
+
+#[derive(Resource)]
+pub struct LevelHandle(pub Handle<LevelData>);
+
+/// Geometry baked by the `bake` subcommand, `None` if the level hasn't been baked
+#[derive(Resource)]
+pub struct BakedLevelHandle(pub Option<Handle<BakedLevel>>);
+
 #[derive(Resource)]
 pub struct InputDir {
     pub dir: Vec2,
@@ -82,30 +149,257 @@ pub struct Physics {
 #[derive(Component)]
 pub struct Player {}
 
-pub fn s_init(mut commands: Commands) {
-    let grid_size = 32.0;
+/// Marks the on-screen text shown when the level fails to load
+#[derive(Component)]
+pub struct LevelErrorText;
 
-    let (level_polygons, size, half_size) = generate_level_polygons(grid_size);
+pub fn s_init(mut commands: Commands, asset_server: Res<AssetServer>, level_path: Res<LevelPath>) {
+    // The level loads asynchronously, start empty until s_apply_level picks it up
+    commands.insert_resource(Level {
+        polygons: Vec::new(),
+        triggers: Vec::new(),
+        theme: LevelTheme::default(),
+        grid_size: DEFAULT_GRID_SIZE,
+        size: Vec2::ZERO,
+        half_size: Vec2::ZERO,
+    });
+    commands.insert_resource(SpatialGrid::new(&[], DEFAULT_GRID_SIZE));
+    commands.insert_resource(LevelHandle(
+        asset_server.load(level_asset_path(&level_path.path)),
+    ));
 
-    // Create spatial grid for efficient raycast queries
-    // Complexity: O(edges) at startup, but enables O(nearby_edges) per-raycast queries
-    let spatial_grid = SpatialGrid::new(&level_polygons, grid_size);
+    // Baking is optional, so only request blobs that exist, the asset server logs an error
+    // for every missing one. The web can't check for files up front, so it uses a fixed list.
+    let baked_path = baked_level_path(&level_path.path);
+    #[cfg(not(target_arch = "wasm32"))]
+    let baked = std::path::Path::new(&baked_path).exists();
+    #[cfg(target_arch = "wasm32")]
+    let baked = WEB_BAKED_LEVELS.contains(&level_path.path.as_str());
+    commands.insert_resource(BakedLevelHandle(
+        baked.then(|| asset_server.load(level_asset_path(&baked_path))),
+    ));
 
-    commands.insert_resource(Level {
-        polygons: level_polygons,
-        grid_size,
+    commands.spawn(Camera2d);
+}
+
+/// The level asset in use, the embedded level if the level file couldn't be read
+#[derive(SystemParam)]
+pub struct CurrentLevel<'w> {
+    handle: Res<'w, LevelHandle>,
+    assets: Res<'w, Assets<LevelData>>,
+}
+
+impl CurrentLevel<'_> {
+    fn get(&self) -> Option<&LevelData> {
+        self.assets.get(&self.handle.0)
+    }
+}
+
+/// Geometry baked ahead of time for the level, if there is any
+#[derive(SystemParam)]
+pub struct BakedGeometry<'w> {
+    handle: Res<'w, BakedLevelHandle>,
+    assets: Res<'w, Assets<BakedLevel>>,
+    asset_server: Res<'w, AssetServer>,
+}
+
+impl BakedGeometry<'_> {
+    /// Whether the baked geometry is still being read
+    fn is_loading(&self) -> bool {
+        self.handle.0.as_ref().is_some_and(|handle| {
+            matches!(
+                self.asset_server.get_load_state(handle),
+                Some(LoadState::Loading)
+            )
+        })
+    }
+
+    fn get(&self) -> Option<&BakedLevel> {
+        self.handle
+            .0
+            .as_ref()
+            .and_then(|handle| self.assets.get(handle))
+    }
+}
+
+/// The on-screen text saying why the level failed to load
+#[derive(SystemParam)]
+pub struct LevelErrorDisplay<'w, 's> {
+    commands: Commands<'w, 's>,
+    text_query: Query<'w, 's, Entity, With<LevelErrorText>>,
+    level_path: Res<'w, LevelPath>,
+}
+
+impl LevelErrorDisplay<'_, '_> {
+    fn show(&mut self, err: &dyn std::fmt::Display) {
+        error!("Failed to load level \"{}\": {err}", self.level_path.path);
+
+        self.clear();
+        self.commands.spawn((
+            Text::new(format!(
+                "Failed to load level \"{}\":\n{err}",
+                self.level_path.path
+            )),
+            TextColor(Color::Srgba(css::RED)),
+            LevelErrorText,
+        ));
+    }
+
+    fn clear(&mut self) {
+        for entity in &self.text_query {
+            self.commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Fall back to the embedded level when the level file can't be read, and show any other
+/// load error
+pub fn s_level_load_failed(
+    mut failed_events: EventReader<AssetLoadFailedEvent<LevelData>>,
+    mut level_handle: ResMut<LevelHandle>,
+    mut level_assets: ResMut<Assets<LevelData>>,
+    mut errors: LevelErrorDisplay,
+) {
+    for event in failed_events.read() {
+        if event.id != level_handle.0.id() {
+            continue;
+        }
+
+        if matches!(event.error, AssetLoadError::AssetReaderError(_)) {
+            // The file couldn't be read, fall back to the embedded level
+            warn!(
+                "Failed to read level file \"{}\" ({}), using embedded level",
+                errors.level_path.path, event.error
+            );
+            match parse_level_data(LEVEL_DATA) {
+                Ok(level_data) => level_handle.0 = level_assets.add(level_data),
+                Err(err) => errors.show(&err),
+            }
+        } else {
+            errors.show(&event.error);
+        }
+    }
+}
+
+/// Rebuild the level geometry whenever the level asset is loaded or changes on disk
+///
+/// Uses the baked geometry if it's up to date, waiting for it to load first.
+pub fn s_apply_level(
+    mut asset_events: EventReader<AssetEvent<LevelData>>,
+    current_level: CurrentLevel,
+    baked: BakedGeometry,
+    mut level_pending: Local<bool>,
+    mut errors: LevelErrorDisplay,
+    mut level: ResMut<Level>,
+    mut spatial_grid: ResMut<SpatialGrid>,
+) {
+    // Assets added directly (the embedded fallback) only get `Added`, loaded ones get both
+    let handle = &current_level.handle.0;
+    let level_changed = asset_events.read().fold(false, |changed, event| {
+        changed || event.is_added(handle) || event.is_modified(handle)
+    });
+    *level_pending |= level_changed;
+
+    // Wait for the baked geometry, so it isn't extracted at startup anyway
+    if !*level_pending || baked.is_loading() {
+        return;
+    }
+    *level_pending = false;
+
+    let Some(level_data) = current_level.get() else {
+        return;
+    };
+
+    let (polygons, size, half_size, grid) = match baked.get() {
+        Some(baked) if baked.is_baked_from(level_data) => (
+            baked.polygons.clone(),
+            baked.size,
+            baked.half_size,
+            baked.spatial_grid(),
+        ),
+        baked => {
+            if baked.is_some() {
+                info!(
+                    "Baked geometry for \"{}\" is out of date, extracting it instead",
+                    errors.level_path.path
+                );
+            }
+
+            let (polygons, size, half_size) = match generate_level_polygons(level_data) {
+                Ok(generated) => generated,
+                Err(err) => {
+                    // Keep the previous geometry so a bad edit doesn't wipe the level
+                    errors.show(&err);
+                    return;
+                }
+            };
+
+            // Create spatial grid for efficient raycast queries
+            // Complexity: O(edges) per load, but enables O(nearby_edges) per-raycast queries
+            let grid = SpatialGrid::new(&polygons, level_data.grid_size);
+            (polygons, size, half_size, grid)
+        }
+    };
+
+    errors.clear();
+    *spatial_grid = grid;
+    *level = Level {
+        polygons,
+        triggers: level_data.triggers.clone(),
+        theme: level_data.theme(),
+        grid_size: level_data.grid_size,
         size,
         half_size,
-    });
+    };
+}
+
+/// Spawn the player and creatures when the level is first applied
+///
+/// On later reloads agents are kept, and any that end up inside solid tiles are moved to the
+/// nearest empty tile.
+pub fn s_place_agents(
+    mut commands: Commands,
+    level: Res<Level>,
+    current_level: CurrentLevel,
+    mut player_pos: ResMut<PlayerPosition>,
+    mut agent_query: Query<(&mut Transform, &mut Physics)>,
+    player_query: Query<(), With<Player>>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+    let Some(level_data) = current_level.get() else {
+        return;
+    };
 
-    commands.insert_resource(spatial_grid);
+    if player_query.is_empty() {
+        spawn_agents(&mut commands, level_data, &mut player_pos);
+        return;
+    }
 
-    commands.spawn(Camera2d);
+    // Move agents out of tiles that became solid
+    let polygons_solid_inside = level_data.polygons_solid_inside(&level.polygons);
+    for (mut transform, mut physics) in agent_query.iter_mut() {
+        let position = transform.translation.xy();
+        if !level_data.is_solid_at(position, &polygons_solid_inside) {
+            continue;
+        }
+        if let Some(open_position) =
+            level_data.nearest_open_position(position, &polygons_solid_inside)
+        {
+            transform.translation = open_position.extend(0.0);
+            physics.prev_position = open_position;
+            physics.velocity = Vec2::ZERO;
+        }
+    }
+}
 
+fn spawn_agents(commands: &mut Commands, level_data: &LevelData, player_pos: &mut PlayerPosition) {
+    player_pos.position = level_data.player_spawn;
     commands.spawn((
-        Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
+        Transform::from_translation(level_data.player_spawn.extend(0.0)),
         Physics {
-            prev_position: Vec2::ZERO,
+            prev_position: level_data.player_spawn,
             velocity: Vec2::ZERO,
             acceleration: Vec2::ZERO,
             radius: PLAYER_RADIUS,
@@ -114,22 +408,25 @@ pub fn s_init(mut commands: Commands) {
         Player {},
     ));
 
-    commands.spawn((
-        Transform::from_translation(AI_SPAWN_POSITION.extend(0.0)),
-        Physics {
-            prev_position: Vec2::ZERO,
-            velocity: Vec2::X,
-            acceleration: Vec2::ZERO,
-            radius: AI_RADIUS,
-            normal: Vec2::ZERO,
-        },
-        FleeAI {
-            dir_weights: [0.0; 16],
-            wander_angle: PI / 2.0,
-            color: Color::Srgba(css::GREEN),
-            blend: 1.0,
-        },
-    ));
+    for spawn in &level_data.creature_spawns {
+        commands.spawn((
+            Name::new(spawn.name.clone()),
+            Transform::from_translation(spawn.position.extend(0.0)),
+            Physics {
+                prev_position: spawn.position,
+                velocity: Vec2::X,
+                acceleration: Vec2::ZERO,
+                radius: AI_RADIUS,
+                normal: Vec2::ZERO,
+            },
+            FleeAI {
+                dir_weights: [0.0; 16],
+                wander_angle: PI / 2.0,
+                color: level_data.theme().creature_color(1.0),
+                blend: 1.0,
+            },
+        ));
+    }
 }
 
 pub fn s_input(
@@ -201,9 +498,11 @@ pub fn s_render(
     flee_ai_query: Query<(&Transform, &Physics, &FleeAI)>,
     gizmos_visible: Res<GizmosVisible>,
 ) {
-    // Draw the level polygons
-    for polygon in &level.polygons {
-        gizmos.linestrip_2d(polygon.points.iter().copied(), polygon.color);
+    // Draw the trigger areas
+    if gizmos_visible.visible {
+        for trigger in &level.triggers {
+            gizmos.rect_2d(trigger.rect.center(), trigger.rect.size(), css::ORANGE);
+        }
     }
 
     // Draw the flee AI
@@ -0,0 +1,148 @@
+use ::bevy::prelude::*;
+use bevy::{
+    asset::RenderAssetUsages,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use crate::{
+    level::{fill::wall_fills, Polygon},
+    s_apply_level, Level,
+};
+
+// Wall drawing parameters
+const OUTLINE_WIDTH: f32 = 2.0;
+/// How far fills are darkened towards black, so outlines stand out against them
+const FILL_DARKEN: f32 = 0.6;
+const FILL_Z: f32 = -2.0;
+const OUTLINE_Z: f32 = -1.0;
+
+/// Plugin drawing the level walls as meshes
+///
+/// Solid areas are filled and every polygon is outlined in its colour. The meshes are rebuilt
+/// whenever `s_apply_level` replaces the level, instead of being redrawn every frame.
+pub struct WallRenderPlugin;
+
+impl Plugin for WallRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, s_build_wall_meshes.after(s_apply_level));
+    }
+}
+
+/// Marks the wall fill and outline meshes
+#[derive(Component)]
+pub struct WallMesh;
+
+pub fn s_build_wall_meshes(
+    mut commands: Commands,
+    level: Res<Level>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    wall_mesh_query: Query<Entity, With<WallMesh>>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    for entity in &wall_mesh_query {
+        commands.entity(entity).despawn();
+    }
+    if level.polygons.is_empty() {
+        return;
+    }
+
+    // Vertex colours tint a white material, so all walls share one mesh per layer
+    let material = materials.add(ColorMaterial::from(Color::WHITE));
+    let bounds = Rect::from_center_half_size(Vec2::ZERO, level.half_size * level.grid_size);
+
+    let mut fill = MeshBuilder::default();
+    for wall_fill in wall_fills(&level.polygons, bounds) {
+        let color = wall_fill
+            .color
+            .to_srgba()
+            .mix(&Srgba::BLACK, FILL_DARKEN)
+            .into();
+        for triangle in wall_fill.triangles {
+            fill.push_polygon(&triangle, color);
+        }
+    }
+
+    let mut outline = MeshBuilder::default();
+    for polygon in &level.polygons {
+        push_outline(&mut outline, polygon);
+    }
+
+    for (builder, z) in [(fill, FILL_Z), (outline, OUTLINE_Z)] {
+        if builder.indices.is_empty() {
+            continue;
+        }
+        commands.spawn((
+            Mesh2d(meshes.add(builder.build())),
+            MeshMaterial2d(material.clone()),
+            Transform::from_xyz(0.0, 0.0, z),
+            WallMesh,
+        ));
+    }
+}
+
+/// Draw each edge of a polygon as a quad, with squares at the corners to close the gaps
+fn push_outline(builder: &mut MeshBuilder, polygon: &Polygon) {
+    let half_width = OUTLINE_WIDTH / 2.0;
+
+    for edge in polygon.points.windows(2) {
+        let offset = (edge[1] - edge[0]).perp().normalize_or_zero() * half_width;
+        builder.push_polygon(
+            &[
+                edge[0] - offset,
+                edge[1] - offset,
+                edge[1] + offset,
+                edge[0] + offset,
+            ],
+            polygon.color,
+        );
+
+        let corner = Vec2::splat(half_width);
+        builder.push_polygon(
+            &[
+                edge[1] - corner,
+                edge[1] + Vec2::new(corner.x, -corner.y),
+                edge[1] + corner,
+                edge[1] + Vec2::new(-corner.x, corner.y),
+            ],
+            polygon.color,
+        );
+    }
+}
+
+/// Collects coloured triangles into a 2D mesh
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Push a convex polygon as a fan of triangles
+    fn push_polygon(&mut self, points: &[Vec2], color: Color) {
+        let first = self.positions.len() as u32;
+        let color = color.to_linear().to_f32_array();
+
+        for point in points {
+            self.positions.push(point.extend(0.0).to_array());
+            self.colors.push(color);
+        }
+        for i in 1..points.len() as u32 - 1 {
+            self.indices.extend([first, first + i, first + i + 1]);
+        }
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
@@ -1,24 +1,110 @@
 use std::collections::HashMap;
 
-use bevy::math::Vec2;
+use bevy::math::{IVec2, Rect, Vec2};
 
-use crate::level::Polygon;
+use crate::{
+    level::Polygon,
+    utils::{closest_point_on_segment, line_intersect},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Rays closer than this (as a fraction of their length) to crossing both grid lines at once
+/// are treated as passing through the corner
+const CORNER_EPSILON: f32 = 1e-6;
+
+/// Index of an edge in the grid, counting every polygon's edges in order
+pub type EdgeId = u32;
+
+/// How directly a circle already touching an edge has to move into it to count as a hit,
+/// so circles resting against a wall can still slide along it
+const SWEEP_TOUCH_EPSILON: f32 = 1e-4;
 
 /// Represents an edge segment for spatial partitioning
 #[derive(Clone, Copy)]
 pub struct Edge {
     pub start: Vec2,
     pub end: Vec2,
+    /// Index of the polygon the edge belongs to
+    pub polygon: u32,
+}
+
+/// The nearest edge hit by a ray
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub point: Vec2,
+    /// Distance from the ray's origin to `point`
+    pub distance: f32,
+    /// Unit normal of the edge, on the side the ray came from
+    pub normal: Vec2,
+    pub edge: EdgeId,
+    pub polygon: u32,
+}
+
+/// The first edge hit by a moving circle
+#[derive(Clone, Copy, Debug)]
+pub struct SweepHit {
+    /// Fraction of the sweep travelled before the hit, 0 if the circle starts against the edge
+    pub time: f32,
+    /// Centre of the circle when it hits
+    pub center: Vec2,
+    /// Unit normal at the contact, pointing from the edge towards the circle
+    pub normal: Vec2,
+    pub edge: EdgeId,
+    pub polygon: u32,
 }
 
 /// Grid-based spatial hash for efficient raycast queries
 ///
 /// Partitions polygon edges into grid cells to reduce raycast complexity
-/// from O(all_edges) to O(nearby_edges).
+/// from O(all_edges) to O(nearby_edges). Each edge is stored once, and cells hold edge IDs.
 #[derive(bevy::prelude::Resource)]
 pub struct SpatialGrid {
     cell_size: f32,
-    grid: HashMap<(i32, i32), Vec<Edge>>,
+    edges: Vec<Edge>,
+    grid: HashMap<(i32, i32), Vec<EdgeId>>,
+    /// Box around every edge, grown by a cell so rays clipped to it keep every cell with edges
+    bounds: Rect,
+}
+
+/// Scratch space for grid queries, kept by the caller so queries don't allocate
+///
+/// Edges are deduplicated by stamping them with the query's generation, so the visited
+/// array never needs clearing between queries.
+#[derive(Default)]
+pub struct EdgeQuery {
+    edges: Vec<EdgeId>,
+    visited: Vec<u32>,
+    generation: u32,
+}
+
+impl EdgeQuery {
+    /// Start a new query over a grid with `edge_count` edges
+    fn begin(&mut self, edge_count: usize) {
+        self.edges.clear();
+        if self.visited.len() < edge_count {
+            self.visited.resize(edge_count, 0);
+        }
+        if self.generation == u32::MAX {
+            self.visited.fill(0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+    }
+
+    /// Add the edges in a cell that this query hasn't seen yet and that pass `keep`
+    fn visit(&mut self, cell_edges: &[EdgeId], mut keep: impl FnMut(EdgeId) -> bool) {
+        for &id in cell_edges {
+            let stamp = &mut self.visited[id as usize];
+            if *stamp != self.generation {
+                *stamp = self.generation;
+                if keep(id) {
+                    self.edges.push(id);
+                }
+            }
+        }
+    }
 }
 
 impl SpatialGrid {
@@ -28,123 +114,473 @@ impl SpatialGrid {
     /// * `polygons` - All polygons in the level
     /// * `cell_size` - Size of each grid cell (should match level grid size for best performance)
     pub fn new(polygons: &[Polygon], cell_size: f32) -> Self {
-        let mut grid: HashMap<(i32, i32), Vec<Edge>> = HashMap::new();
+        let edges = Self::polygon_edges(polygons);
+        let mut grid: HashMap<(i32, i32), Vec<EdgeId>> = HashMap::new();
 
         // Insert all edges into grid cells
-        for polygon in polygons {
-            for i in 1..polygon.points.len() {
-                let start = polygon.points[i - 1];
-                let end = polygon.points[i];
-                let edge = Edge { start, end };
-
-                // Find all grid cells this edge intersects
-                let cells = Self::cells_for_edge(start, end, cell_size);
-                for cell in cells {
-                    grid.entry(cell).or_default().push(edge);
-                }
+        for (id, edge) in edges.iter().enumerate() {
+            // Find all grid cells this edge intersects
+            for cell in Self::cells_for_edge(edge.start, edge.end, cell_size) {
+                grid.entry(cell).or_default().push(id as EdgeId);
             }
         }
 
-        Self { cell_size, grid }
+        Self {
+            cell_size,
+            bounds: Self::edge_bounds(&edges, cell_size),
+            edges,
+            grid,
+        }
+    }
+
+    /// Every polygon's edges in order, so an edge's index is its ID
+    pub fn polygon_edges(polygons: &[Polygon]) -> Vec<Edge> {
+        polygons
+            .iter()
+            .enumerate()
+            .flat_map(|(i, polygon)| {
+                polygon.points.windows(2).map(move |edge| Edge {
+                    start: edge[0],
+                    end: edge[1],
+                    polygon: i as u32,
+                })
+            })
+            .collect()
+    }
+
+    /// Create a spatial grid from cells built earlier, e.g. by baking the level
+    ///
+    /// Cell edge IDs index `edges`, and IDs past the end are dropped.
+    pub fn from_cells(
+        cell_size: f32,
+        edges: Vec<Edge>,
+        cells: impl IntoIterator<Item = ((i32, i32), Vec<EdgeId>)>,
+    ) -> Self {
+        let grid = cells
+            .into_iter()
+            .map(|(cell, mut cell_edges)| {
+                cell_edges.retain(|&id| (id as usize) < edges.len());
+                (cell, cell_edges)
+            })
+            .collect();
+
+        Self {
+            cell_size,
+            bounds: Self::edge_bounds(&edges, cell_size),
+            edges,
+            grid,
+        }
+    }
+
+    /// Box around every edge, grown by a cell on each side
+    fn edge_bounds(edges: &[Edge], cell_size: f32) -> Rect {
+        let bounds = edges.iter().fold(Rect::EMPTY, |bounds, edge| {
+            bounds.union_point(edge.start).union_point(edge.end)
+        });
+        bounds.inflate(cell_size)
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Get an edge by its ID
+    pub fn edge(&self, id: EdgeId) -> Edge {
+        self.edges[id as usize]
+    }
+
+    /// Every non-empty cell and the IDs of the edges in it, in no particular order
+    pub fn cells(&self) -> impl Iterator<Item = ((i32, i32), &[EdgeId])> {
+        self.grid
+            .iter()
+            .map(|(&cell, edges)| (cell, edges.as_slice()))
     }
 
     /// Get all edges that potentially intersect a ray
     ///
-    /// Returns edges in grid cells along the ray path.
-    /// This reduces the number of edge tests from O(all_edges) to O(cells_along_ray × edges_per_cell).
-    pub fn edges_along_ray(&self, start: Vec2, end: Vec2) -> Vec<Edge> {
-        let mut edges = Vec::new();
-        let mut seen = std::collections::HashSet::new();
+    /// Returns the IDs of edges in grid cells along the ray path, each once, in the order
+    /// their cells are reached. This reduces the number of edge tests from O(all_edges) to
+    /// O(cells_along_ray × edges_per_cell). The walk stops where the ray leaves the level, so
+    /// long rays cost no more than rays to its far side.
+    pub fn edges_along_ray<'a>(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        query: &'a mut EdgeQuery,
+    ) -> &'a [EdgeId] {
+        query.begin(self.edges.len());
+        let Some((_, t_exit)) = clip_segment_to_aabb(start, end, self.bounds.min, self.bounds.max)
+        else {
+            return &query.edges;
+        };
 
         // Walk through grid cells along the ray
-        let cells = Self::cells_for_ray(start, end, self.cell_size);
-        for cell in cells {
+        let mut cells = CellWalk::new(start, end, self.cell_size);
+        while let Some(cell) = cells.next() {
             if let Some(cell_edges) = self.grid.get(&cell) {
-                for edge in cell_edges {
-                    // Use edge endpoints as key to avoid duplicates
-                    let key = (
-                        (edge.start.x * 1000.0) as i32,
-                        (edge.start.y * 1000.0) as i32,
-                        (edge.end.x * 1000.0) as i32,
-                        (edge.end.y * 1000.0) as i32,
-                    );
-                    if seen.insert(key) {
-                        edges.push(*edge);
+                query.visit(cell_edges, |_| true);
+            }
+            if cells.cell_exit >= t_exit {
+                break;
+            }
+        }
+
+        &query.edges
+    }
+
+    /// Get all edges that come within `radius` of `center`
+    pub fn edges_in_circle<'a>(
+        &self,
+        center: Vec2,
+        radius: f32,
+        query: &'a mut EdgeQuery,
+    ) -> &'a [EdgeId] {
+        self.edges_in_cells(
+            center - radius,
+            center + radius,
+            |edge| {
+                closest_point_on_segment(edge.start, edge.end, center).distance_squared(center)
+                    <= radius * radius
+            },
+            query,
+        )
+    }
+
+    /// Get all edges that touch the box from `min` to `max`
+    pub fn edges_in_aabb<'a>(
+        &self,
+        min: Vec2,
+        max: Vec2,
+        query: &'a mut EdgeQuery,
+    ) -> &'a [EdgeId] {
+        self.edges_in_cells(
+            min,
+            max,
+            |edge| segment_intersects_aabb(edge.start, edge.end, min, max),
+            query,
+        )
+    }
+
+    /// Get the edges in the cells overlapping the box from `min` to `max` that pass `keep`
+    fn edges_in_cells<'a>(
+        &self,
+        min: Vec2,
+        max: Vec2,
+        keep: impl Fn(&Edge) -> bool,
+        query: &'a mut EdgeQuery,
+    ) -> &'a [EdgeId] {
+        query.begin(self.edges.len());
+        let keep = |id: EdgeId| keep(&self.edges[id as usize]);
+
+        let min_cell = (min / self.cell_size).floor().as_ivec2();
+        let max_cell = (max / self.cell_size).floor().as_ivec2();
+        let range = (max_cell - min_cell + 1).max(IVec2::ZERO);
+
+        if (range.x as usize).saturating_mul(range.y as usize) > self.grid.len() {
+            // Larger than the level, so check the cells there are instead
+            for (&(x, y), cell_edges) in &self.grid {
+                if (min_cell.x..=max_cell.x).contains(&x) && (min_cell.y..=max_cell.y).contains(&y)
+                {
+                    query.visit(cell_edges, keep);
+                }
+            }
+        } else {
+            for x in min_cell.x..=max_cell.x {
+                for y in min_cell.y..=max_cell.y {
+                    if let Some(cell_edges) = self.grid.get(&(x, y)) {
+                        query.visit(cell_edges, keep);
                     }
                 }
             }
         }
 
-        edges
+        &query.edges
     }
 
-    /// Find all grid cells that an edge intersects
-    fn cells_for_edge(start: Vec2, end: Vec2, cell_size: f32) -> Vec<(i32, i32)> {
-        let mut cells = std::collections::HashSet::new();
+    /// Find the nearest edge a ray hits within `max_distance` of its origin
+    ///
+    /// Cells are tested front to back, stopping at the first cell the nearest hit so far lies
+    /// within, so walls behind the hit are never tested. Edges spanning several cells are only
+    /// tested once.
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        query: &mut EdgeQuery,
+    ) -> Option<RayHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO || max_distance <= 0.0 {
+            return None;
+        }
+        let end = origin + direction * max_distance;
+        query.begin(self.edges.len());
 
-        // Get cell coordinates for endpoints
-        let start_cell = (
-            (start.x / cell_size).floor() as i32,
-            (start.y / cell_size).floor() as i32,
-        );
-        let end_cell = (
-            (end.x / cell_size).floor() as i32,
-            (end.y / cell_size).floor() as i32,
-        );
+        let mut nearest: Option<RayHit> = None;
+        let mut cells = CellWalk::new(origin, end, self.cell_size);
+        while let Some(cell) = cells.next() {
+            let first_new = query.edges.len();
+            if let Some(cell_edges) = self.grid.get(&cell) {
+                query.visit(cell_edges, |_| true);
+            }
 
-        cells.insert(start_cell);
-        cells.insert(end_cell);
+            for &id in &query.edges[first_new..] {
+                let edge = self.edges[id as usize];
+                let Some(point) = line_intersect(origin, end, edge.start, edge.end) else {
+                    continue;
+                };
+                let distance = point.distance(origin);
+                if nearest.is_some_and(|nearest| nearest.distance <= distance) {
+                    continue;
+                }
 
-        // Walk along the edge using Bresenham-like algorithm
-        let dx = end.x - start.x;
-        let dy = end.y - start.y;
-        let steps = (dx.abs().max(dy.abs()) / cell_size).ceil() as i32 + 1;
+                let mut normal = (edge.end - edge.start).perp().normalize_or_zero();
+                if normal.dot(direction) > 0.0 {
+                    normal = -normal;
+                }
+                nearest = Some(RayHit {
+                    point,
+                    distance,
+                    normal,
+                    edge: id,
+                    polygon: edge.polygon,
+                });
+            }
 
-        for i in 0..=steps {
-            let t = if steps > 0 {
-                i as f32 / steps as f32
-            } else {
-                0.0
+            // Every later cell is further along the ray than this one
+            if nearest.is_some_and(|nearest| nearest.distance <= cells.cell_exit * max_distance) {
+                break;
+            }
+        }
+
+        nearest
+    }
+
+    /// Find the first edge a circle of `radius` hits moving up to `max_distance` from `origin`
+    ///
+    /// Unlike a ray this won't pass through gaps narrower than the circle. A circle that
+    /// starts touching an edge only hits it if it's moving into the edge.
+    pub fn sweep_circle(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        radius: f32,
+        query: &mut EdgeQuery,
+    ) -> Option<SweepHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO || max_distance <= 0.0 {
+            return None;
+        }
+        let end = origin + direction * max_distance;
+
+        let mut first: Option<SweepHit> = None;
+        for &id in self.edges_in_aabb(origin.min(end) - radius, origin.max(end) + radius, query) {
+            let edge = self.edges[id as usize];
+            let Some((distance, normal)) = sweep_circle_edge(origin, direction, radius, edge)
+            else {
+                continue;
             };
-            let x = start.x + dx * t;
-            let y = start.y + dy * t;
-            let cell = (
-                (x / cell_size).floor() as i32,
-                (y / cell_size).floor() as i32,
-            );
-            cells.insert(cell);
+            if distance > max_distance
+                || first.is_some_and(|first| first.time * max_distance <= distance)
+            {
+                continue;
+            }
+
+            first = Some(SweepHit {
+                time: distance / max_distance,
+                center: origin + direction * distance,
+                normal,
+                edge: id,
+                polygon: edge.polygon,
+            });
         }
 
-        cells.into_iter().collect()
+        first
     }
 
-    /// Find all grid cells that a ray passes through
-    fn cells_for_ray(start: Vec2, end: Vec2, cell_size: f32) -> Vec<(i32, i32)> {
-        let mut cells = Vec::new();
-        let mut seen = std::collections::HashSet::new();
+    /// Find all grid cells that an edge intersects
+    ///
+    /// Uses the same traversal as rays, so any point where a ray touches an edge is in a cell
+    /// both of them visit.
+    fn cells_for_edge(start: Vec2, end: Vec2, cell_size: f32) -> CellWalk {
+        CellWalk::new(start, end, cell_size)
+    }
+}
 
-        let dx = end.x - start.x;
-        let dy = end.y - start.y;
-        let length = (dx * dx + dy * dy).sqrt();
-        let steps = (length / cell_size).ceil() as i32 + 1;
+/// Distance a circle moving along the unit `direction` travels before touching an edge, and
+/// the contact normal
+fn sweep_circle_edge(
+    origin: Vec2,
+    direction: Vec2,
+    radius: f32,
+    edge: Edge,
+) -> Option<(f32, Vec2)> {
+    // Already touching, a hit only when moving into the edge
+    let closest = closest_point_on_segment(edge.start, edge.end, origin);
+    if closest.distance_squared(origin) <= radius * radius {
+        let normal = (origin - closest).normalize_or_zero();
+        return (direction.dot(normal) < -SWEEP_TOUCH_EPSILON).then_some((0.0, normal));
+    }
 
-        for i in 0..=steps {
-            let t = if steps > 0 {
-                i as f32 / steps as f32
+    // The edge's side, from the normal facing the circle
+    let line = edge.end - edge.start;
+    let mut normal = line.perp().normalize_or_zero();
+    if normal.dot(origin - edge.start) < 0.0 {
+        normal = -normal;
+    }
+    let approach_speed = -direction.dot(normal);
+    let mut first: Option<(f32, Vec2)> = None;
+    if approach_speed > 0.0 {
+        let distance = (normal.dot(origin - edge.start) - radius) / approach_speed;
+        let contact = origin + direction * distance - normal * radius;
+        let along = (contact - edge.start).dot(line) / line.length_squared();
+        if distance >= 0.0 && (0.0..=1.0).contains(&along) {
+            first = Some((distance, normal));
+        }
+    }
+
+    // The edge's ends, where the circle's path first comes within `radius` of them
+    for point in [edge.start, edge.end] {
+        let to_circle = origin - point;
+        let b = to_circle.dot(direction);
+        let discriminant = b * b - (to_circle.length_squared() - radius * radius);
+        if b >= 0.0 || discriminant < 0.0 {
+            continue;
+        }
+        let distance = -b - discriminant.sqrt();
+        if first.is_none_or(|(first, _)| distance < first) {
+            let center = origin + direction * distance;
+            first = Some((distance, (center - point).normalize_or_zero()));
+        }
+    }
+
+    first
+}
+
+/// Whether a segment touches the box from `min` to `max`
+fn segment_intersects_aabb(start: Vec2, end: Vec2, min: Vec2, max: Vec2) -> bool {
+    clip_segment_to_aabb(start, end, min, max).is_some()
+}
+
+/// Distances along a segment (0 at start, 1 at end) where it enters and leaves the box from
+/// `min` to `max`, or `None` if it misses the box
+fn clip_segment_to_aabb(start: Vec2, end: Vec2, min: Vec2, max: Vec2) -> Option<(f32, f32)> {
+    let delta = end - start;
+    let (mut t_enter, mut t_exit) = (0.0f32, 1.0f32);
+
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            if start[axis] < min[axis] || start[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let a = (min[axis] - start[axis]) / delta[axis];
+        let b = (max[axis] - start[axis]) / delta[axis];
+        t_enter = t_enter.max(a.min(b));
+        t_exit = t_exit.min(a.max(b));
+    }
+
+    (t_enter <= t_exit).then_some((t_enter, t_exit))
+}
+
+/// Iterator over the grid cells a ray passes through, in order from its start
+///
+/// Steps from cell to cell across whichever grid line the ray reaches next (Amanatides–Woo
+/// traversal), so no cell is skipped however the ray clips it. A ray through a cell corner
+/// also visits the two cells it only touches at the corner.
+struct CellWalk {
+    cell: (i32, i32),
+    step: (i32, i32),
+    /// Distance along the ray (0 at start, 1 at end) to the next grid line on each axis
+    t_max: Vec2,
+    /// Distance along the ray between grid lines on each axis
+    t_delta: Vec2,
+    /// Cells still to yield from the last step, in reverse order
+    pending: [(i32, i32); 3],
+    pending_count: usize,
+    /// Distance along the ray of the last corner passed through
+    corner_t: f32,
+    /// Distance along the ray at which it leaves the last cell yielded
+    cell_exit: f32,
+}
+
+impl CellWalk {
+    fn new(start: Vec2, end: Vec2, cell_size: f32) -> Self {
+        let delta = end - start;
+        let cell = (
+            (start.x / cell_size).floor() as i32,
+            (start.y / cell_size).floor() as i32,
+        );
+
+        let axis = |position: f32, delta: f32, cell: i32| -> (i32, f32, f32) {
+            if delta > 0.0 {
+                let next_line = (cell + 1) as f32 * cell_size;
+                (1, (next_line - position) / delta, cell_size / delta)
+            } else if delta < 0.0 {
+                let next_line = cell as f32 * cell_size;
+                (-1, (next_line - position) / delta, -cell_size / delta)
             } else {
-                0.0
-            };
-            let x = start.x + dx * t;
-            let y = start.y + dy * t;
-            let cell = (
-                (x / cell_size).floor() as i32,
-                (y / cell_size).floor() as i32,
-            );
-            if seen.insert(cell) {
-                cells.push(cell);
+                (0, f32::INFINITY, f32::INFINITY)
             }
+        };
+        let (step_x, t_max_x, t_delta_x) = axis(start.x, delta.x, cell.0);
+        let (step_y, t_max_y, t_delta_y) = axis(start.y, delta.y, cell.1);
+
+        Self {
+            cell,
+            step: (step_x, step_y),
+            t_max: Vec2::new(t_max_x, t_max_y),
+            t_delta: Vec2::new(t_delta_x, t_delta_y),
+            pending: [cell; 3],
+            pending_count: 1,
+            corner_t: 0.0,
+            cell_exit: 0.0,
+        }
+    }
+}
+
+impl Iterator for CellWalk {
+    type Item = (i32, i32);
+
+    fn next(&mut self) -> Option<(i32, i32)> {
+        if self.pending_count > 0 {
+            self.pending_count -= 1;
+            // The cells beside a corner are only touched at the corner
+            self.cell_exit = if self.pending_count > 0 {
+                self.corner_t
+            } else {
+                self.t_max.min_element()
+            };
+            return Some(self.pending[self.pending_count]);
+        }
+        if self.t_max.min_element() > 1.0 {
+            return None;
+        }
+
+        let (cell, step) = (self.cell, self.step);
+        if (self.t_max.x - self.t_max.y).abs() <= CORNER_EPSILON {
+            // Through a corner, touching the cells either side of it
+            self.corner_t = self.t_max.max_element();
+            self.cell = (cell.0 + step.0, cell.1 + step.1);
+            self.t_max += self.t_delta;
+            self.pending = [
+                self.cell,
+                (cell.0, cell.1 + step.1),
+                (cell.0 + step.0, cell.1),
+            ];
+            self.pending_count = 3;
+            return self.next();
+        } else if self.t_max.x < self.t_max.y {
+            self.cell.0 += step.0;
+            self.t_max.x += self.t_delta.x;
+        } else {
+            self.cell.1 += step.1;
+            self.t_max.y += self.t_delta.y;
         }
 
-        cells
+        self.cell_exit = self.t_max.min_element();
+        Some(self.cell)
     }
 }
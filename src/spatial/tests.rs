@@ -0,0 +1,290 @@
+//! Tests for the grid traversal and queries
+//!
+//! Grids use a cell size of 1 unless a test needs otherwise.
+
+use bevy::{color::Color, math::Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::*;
+
+/// Whether a segment passes through the inside of a cell, not just along its boundary
+fn crosses_cell_interior(start: Vec2, end: Vec2, (x, y): (i32, i32), cell_size: f32) -> bool {
+    let min = Vec2::new(x as f32, y as f32) * cell_size;
+    let max = min + cell_size;
+    let delta = end - start;
+
+    // Clip the segment to the cell on each axis
+    let (mut t_enter, mut t_exit) = (0.0f32, 1.0f32);
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            if start[axis] <= min[axis] || start[axis] >= max[axis] {
+                return false;
+            }
+            continue;
+        }
+        let a = (min[axis] - start[axis]) / delta[axis];
+        let b = (max[axis] - start[axis]) / delta[axis];
+        t_enter = t_enter.max(a.min(b));
+        t_exit = t_exit.min(a.max(b));
+    }
+
+    t_enter < t_exit
+}
+
+fn walk(start: Vec2, end: Vec2, cell_size: f32) -> Vec<(i32, i32)> {
+    CellWalk::new(start, end, cell_size).collect()
+}
+
+#[test]
+fn ray_through_corners_visits_touching_cells() {
+    let cells = walk(Vec2::new(0.5, 0.5), Vec2::new(2.5, 2.5), 1.0);
+    assert_eq!(
+        cells,
+        [(0, 0), (1, 0), (0, 1), (1, 1), (2, 1), (1, 2), (2, 2)]
+    );
+
+    let cells = walk(Vec2::new(2.5, 0.5), Vec2::new(0.5, 2.5), 1.0);
+    assert_eq!(
+        cells,
+        [(2, 0), (1, 0), (2, 1), (1, 1), (0, 1), (1, 2), (0, 2)]
+    );
+
+    // Starting and ending exactly on corners
+    let cells = walk(Vec2::new(0.0, 0.0), Vec2::new(3.0, 3.0), 1.0);
+    assert_eq!(
+        cells,
+        [
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (1, 1),
+            (2, 1),
+            (1, 2),
+            (2, 2),
+            (3, 2),
+            (2, 3),
+            (3, 3)
+        ]
+    );
+}
+
+#[test]
+fn ray_visits_every_cell_it_crosses_in_order() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let cell_size = 3.0;
+
+    for _ in 0..1000 {
+        let start = Vec2::new(rng.random_range(-20.0..20.0), rng.random_range(-20.0..20.0));
+        let end = Vec2::new(rng.random_range(-20.0..20.0), rng.random_range(-20.0..20.0));
+        let cells = walk(start, end, cell_size);
+
+        for x in -8..8 {
+            for y in -8..8 {
+                if crosses_cell_interior(start, end, (x, y), cell_size) {
+                    assert!(
+                        cells.contains(&(x, y)),
+                        "{start} to {end} skipped cell ({x}, {y})"
+                    );
+                }
+            }
+        }
+
+        // Each step moves to a neighbouring cell, without doubling back
+        for pair in cells.windows(2) {
+            let step = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+            assert!(step.0.abs() + step.1.abs() >= 1 && step.0.abs().max(step.1.abs()) == 1);
+        }
+        let last = cells.last().unwrap();
+        assert_eq!(
+            *last,
+            (
+                (end.x / cell_size).floor() as i32,
+                (end.y / cell_size).floor() as i32
+            )
+        );
+    }
+}
+
+fn square_grid() -> SpatialGrid {
+    let polygon = Polygon {
+        points: vec![
+            Vec2::new(0.5, 0.5),
+            Vec2::new(2.5, 0.5),
+            Vec2::new(2.5, 2.5),
+            Vec2::new(0.5, 2.5),
+            Vec2::new(0.5, 0.5),
+        ],
+        collision_side: 1.0,
+        color: Color::WHITE,
+    };
+    SpatialGrid::new(&[polygon], 1.0)
+}
+
+#[test]
+fn edges_along_ray_are_unique_and_in_cell_order() {
+    let grid = square_grid();
+    let mut query = EdgeQuery::default();
+
+    let across = (Vec2::new(-1.0, 1.5), Vec2::new(4.0, 1.5));
+    assert_eq!(grid.edges_along_ray(across.0, across.1, &mut query), [3, 1]);
+    assert_eq!(grid.edges_along_ray(across.1, across.0, &mut query), [1, 3]);
+
+    // The bottom edge is in every cell of the bottom row, but only reported once
+    let bottom_row = (Vec2::new(-1.0, 0.7), Vec2::new(4.0, 0.7));
+    for _ in 0..2 {
+        assert_eq!(
+            grid.edges_along_ray(bottom_row.0, bottom_row.1, &mut query),
+            [0, 3, 1]
+        );
+    }
+
+    let outside = (Vec2::new(-1.0, 5.0), Vec2::new(4.0, 5.0));
+    assert!(grid
+        .edges_along_ray(outside.0, outside.1, &mut query)
+        .is_empty());
+}
+
+#[test]
+fn raycast_finds_nearest_hit_and_facing_normal() {
+    let grid = square_grid();
+    let mut query = EdgeQuery::default();
+
+    let hit = grid
+        .raycast(Vec2::new(1.5, 1.5), Vec2::X, 5.0, &mut query)
+        .unwrap();
+    assert_eq!(hit.point, Vec2::new(2.5, 1.5));
+    assert_eq!(hit.distance, 1.0);
+    assert_eq!(hit.normal, Vec2::NEG_X);
+    assert_eq!((hit.edge, hit.polygon), (1, 0));
+
+    // The far side of the square is in a later cell and never reported
+    let hit = grid
+        .raycast(Vec2::new(-1.0, 1.5), Vec2::new(2.0, 0.0), 5.0, &mut query)
+        .unwrap();
+    assert_eq!((hit.point, hit.distance), (Vec2::new(0.5, 1.5), 1.5));
+    assert_eq!((hit.normal, hit.edge), (Vec2::NEG_X, 3));
+
+    assert!(grid
+        .raycast(Vec2::new(-1.0, 1.5), Vec2::X, 1.0, &mut query)
+        .is_none());
+    assert!(grid
+        .raycast(Vec2::new(-1.0, 1.5), Vec2::ZERO, 5.0, &mut query)
+        .is_none());
+}
+
+#[test]
+fn raycast_matches_testing_every_edge() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let polygons: Vec<Polygon> = (0..20)
+        .map(|_| {
+            let center = Vec2::new(rng.random_range(-20.0..20.0), rng.random_range(-20.0..20.0));
+            let mut points: Vec<Vec2> = (0..5)
+                .map(|i| center + Vec2::from_angle(i as f32 * 1.2566) * rng.random_range(1.0..4.0))
+                .collect();
+            points.push(points[0]);
+            Polygon {
+                points,
+                collision_side: 1.0,
+                color: Color::WHITE,
+            }
+        })
+        .collect();
+    let grid = SpatialGrid::new(&polygons, 3.0);
+    let edges = SpatialGrid::polygon_edges(&polygons);
+    let mut query = EdgeQuery::default();
+
+    for _ in 0..1000 {
+        let origin = Vec2::new(rng.random_range(-25.0..25.0), rng.random_range(-25.0..25.0));
+        let direction = Vec2::from_angle(rng.random_range(0.0..std::f32::consts::TAU));
+        let max_distance = rng.random_range(0.0..40.0);
+
+        let end = origin + direction * max_distance;
+        let nearest = edges
+            .iter()
+            .filter_map(|edge| line_intersect(origin, end, edge.start, edge.end))
+            .map(|point| point.distance(origin))
+            .min_by(f32::total_cmp);
+        let hit = grid.raycast(origin, direction, max_distance, &mut query);
+
+        match (hit, nearest) {
+            (Some(hit), Some(nearest)) => assert!((hit.distance - nearest).abs() < 1e-4),
+            (hit, nearest) => assert_eq!(hit.is_some(), nearest.is_some()),
+        }
+    }
+}
+
+#[test]
+fn edges_in_circle_and_aabb_are_exact() {
+    let grid = square_grid();
+    let mut query = EdgeQuery::default();
+    let sorted = |edges: &[EdgeId]| {
+        let mut edges = edges.to_vec();
+        edges.sort();
+        edges
+    };
+
+    let circle = |center: Vec2, radius: f32, query: &mut EdgeQuery| {
+        sorted(grid.edges_in_circle(center, radius, query))
+    };
+    assert_eq!(circle(Vec2::new(1.5, 0.5), 0.2, &mut query), [0]);
+    assert_eq!(circle(Vec2::new(0.4, 0.4), 0.2, &mut query), [0, 3]);
+    assert!(circle(Vec2::new(1.5, 1.5), 0.5, &mut query).is_empty());
+    assert_eq!(circle(Vec2::new(1.5, 1.5), 1.0, &mut query), [0, 1, 2, 3]);
+
+    let aabb =
+        |min: Vec2, max: Vec2, query: &mut EdgeQuery| sorted(grid.edges_in_aabb(min, max, query));
+    assert_eq!(aabb(Vec2::splat(2.0), Vec2::splat(3.0), &mut query), [1, 2]);
+    assert!(aabb(Vec2::splat(1.0), Vec2::splat(2.0), &mut query).is_empty());
+    // Larger than the level, so every cell is checked instead of every cell in the box
+    assert_eq!(
+        aabb(Vec2::splat(-100.0), Vec2::splat(100.0), &mut query),
+        [0, 1, 2, 3]
+    );
+}
+
+#[test]
+fn sweep_circle_hits_walls_and_narrow_gaps() {
+    let grid = square_grid();
+    let mut query = EdgeQuery::default();
+
+    let hit = grid
+        .sweep_circle(Vec2::new(1.5, 1.5), Vec2::X, 5.0, 0.25, &mut query)
+        .unwrap();
+    assert_eq!((hit.time, hit.center), (0.15, Vec2::new(2.25, 1.5)));
+    assert_eq!((hit.normal, hit.edge), (Vec2::NEG_X, 1));
+
+    // Resting on the bottom edge, sliding along it is fine but moving into it isn't
+    let resting = Vec2::new(1.5, 0.75);
+    let hit = grid
+        .sweep_circle(resting, Vec2::X, 5.0, 0.25, &mut query)
+        .unwrap();
+    assert_eq!((hit.edge, hit.center), (1, Vec2::new(2.25, 0.75)));
+    let hit = grid
+        .sweep_circle(resting, Vec2::NEG_Y, 5.0, 0.25, &mut query)
+        .unwrap();
+    assert_eq!((hit.time, hit.normal, hit.edge), (0.0, Vec2::Y, 0));
+
+    // A gap narrower than the circle lets rays through but stops the circle at its corners
+    let block = |min_x: f32| Polygon {
+        points: vec![
+            Vec2::new(min_x, 0.0),
+            Vec2::new(min_x + 1.0, 0.0),
+            Vec2::new(min_x + 1.0, 1.0),
+            Vec2::new(min_x, 1.0),
+            Vec2::new(min_x, 0.0),
+        ],
+        collision_side: 1.0,
+        color: Color::WHITE,
+    };
+    let grid = SpatialGrid::new(&[block(0.0), block(1.4)], 1.0);
+    let origin = Vec2::new(1.2, -2.0);
+    assert!(grid.raycast(origin, Vec2::Y, 5.0, &mut query).is_none());
+    let hit = grid
+        .sweep_circle(origin, Vec2::Y, 5.0, 0.25, &mut query)
+        .unwrap();
+    assert!((hit.center.y - -0.15).abs() < 1e-5);
+    assert!(hit.normal.y < 0.0);
+    assert!(grid
+        .sweep_circle(origin, Vec2::Y, 5.0, 0.15, &mut query)
+        .is_none());
+}
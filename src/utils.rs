@@ -27,6 +27,18 @@ pub fn line_intersect(
     }
 }
 
+/// The point on the segment from `start` to `end` nearest to `point`
+pub fn closest_point_on_segment(start: Vec2, end: Vec2, point: Vec2) -> Vec2 {
+    let line = end - start;
+    let length_sq = line.length_squared();
+    if length_sq == 0.0 {
+        return start;
+    }
+
+    let t = ((point - start).dot(line) / length_sq).clamp(0.0, 1.0);
+    start + line * t
+}
+
 pub fn cross_product(a: Vec2, b: Vec2) -> f32 {
     a.x * b.y - a.y * b.x
 }